@@ -0,0 +1,397 @@
+use near_sdk::json_types::{U128, U64};
+use near_sdk::{env, near, require, AccountId};
+
+use crate::events::ContractEvent;
+use crate::history::ClaimSource;
+use crate::pause::PausableFeature;
+use crate::roles::Role;
+use crate::{assert_one_yocto, Contract, ContractExt};
+
+/// Fixed-point scale of `reward_per_share`.
+const REWARD_PER_SHARE_PRECISION: u128 = 1_000_000_000_000;
+
+/// Further epochs of the same pool that must be finalized before a finalized
+/// epoch can be closed and its unclaimed reward returned to the pool.
+pub const EPOCH_CLAIM_WINDOW: u64 = 3;
+
+/// One pool epoch in the pro-rata distribution engine. Participants accrue
+/// weight while the epoch is open; finalizing it locks the pool amount and
+/// fixes the reward per unit of weight.
+#[near(serializers = [borsh, json])]
+#[derive(Clone, Default)]
+pub struct EpochDistribution {
+    pub pool_id: u32,
+    pub epoch: u64,
+    pub total_weight: U128,
+    /// Pool amount locked for this epoch when it was finalized.
+    pub reward: U128,
+    /// `reward * 10^12 / total_weight`, rounded down.
+    pub reward_per_share: U128,
+    /// Amount paid out so far.
+    pub claimed: U128,
+    /// Weight whose rewards have been claimed so far.
+    pub claimed_weight: U128,
+    /// Time (nanoseconds) the epoch was finalized, `None` while it is open.
+    pub finalized_at: Option<U64>,
+    /// Time (nanoseconds) the claim window was closed; no claims after that.
+    pub closed_at: Option<U64>,
+}
+
+impl EpochDistribution {
+    /// Reward owed for `weight`, rounded down.
+    fn share_of(&self, weight: u128) -> u128 {
+        weight
+            .checked_mul(self.reward_per_share.0)
+            .expect("Epoch share overflow")
+            / REWARD_PER_SHARE_PRECISION
+    }
+}
+
+#[near]
+impl Contract {
+    /// Close the current epoch of `pool_id` and lock the pool's amount as its
+    /// reward, after applying any refill that was already due. The reward is
+    /// capped by what the pool's epoch claim limit still allows, and a reward
+    /// above the multisig claim limit must go through `propose_action`. If no
+    /// weight accrued, nothing is locked. `RewardDistributor` or `GameServer`.
+    #[payable]
+    pub fn finalize_epoch(&mut self, pool_id: u32) {
+        assert_one_yocto();
+        self.assert_epoch_finalizer();
+        self.internal_refill_before_claim(pool_id);
+        self.multisig_assert_not_required_for_claim(self.internal_epoch_reward(pool_id));
+        self.internal_finalize_epoch(pool_id);
+    }
+
+    /// Claim the caller's pro-rata share of a finalized pool epoch. Attach
+    /// enough NEAR to register for storage if needed.
+    #[payable]
+    pub fn claim_epoch_rewards(&mut self, pool_id: u32, epoch: u64) -> U128 {
+        self.assert_not_paused(PausableFeature::ClaimRewards);
+        let account_id = env::predecessor_account_id();
+        U128(self.internal_claim_epoch_rewards(pool_id, epoch, &account_id))
+    }
+
+    /// Close the claim window of a finalized epoch once `EPOCH_CLAIM_WINDOW`
+    /// later epochs of the pool have been finalized. The unclaimed reward,
+    /// rounding dust included, goes back to the pool and remaining weight can
+    /// no longer be claimed. Anyone can call this. Returns the amount returned.
+    pub fn close_epoch_distribution(&mut self, pool_id: u32, epoch: u64) -> U128 {
+        U128(self.internal_close_epoch_distribution(pool_id, epoch))
+    }
+
+    /// The epoch of `pool_id` currently accruing weight
+    pub fn get_current_distribution_epoch(&self, pool_id: u32) -> u64 {
+        self.internal_current_distribution_epoch(pool_id)
+    }
+
+    /// Totals and reward of a pool epoch, if any weight accrued or it was finalized
+    pub fn get_epoch_distribution(&self, pool_id: u32, epoch: u64) -> Option<EpochDistribution> {
+        self.epoch_distributions.get(&(pool_id, epoch)).cloned()
+    }
+
+    /// Unclaimed weight `account_id` has in a pool epoch
+    pub fn get_epoch_weight(&self, pool_id: u32, epoch: u64, account_id: AccountId) -> U128 {
+        U128(
+            self.epoch_weights
+                .get(&(pool_id, epoch, account_id))
+                .copied()
+                .unwrap_or(0),
+        )
+    }
+
+    /// What `account_id` can claim from a finalized pool epoch
+    pub fn get_claimable_epoch_rewards(
+        &self,
+        pool_id: u32,
+        epoch: u64,
+        account_id: AccountId,
+    ) -> U128 {
+        let weight = self
+            .epoch_weights
+            .get(&(pool_id, epoch, account_id))
+            .copied()
+            .unwrap_or(0);
+        U128(
+            self.epoch_distributions
+                .get(&(pool_id, epoch))
+                .filter(|distribution| {
+                    distribution.finalized_at.is_some() && distribution.closed_at.is_none()
+                })
+                .map_or(0, |distribution| distribution.share_of(weight)),
+        )
+    }
+}
+
+impl Contract {
+    pub(crate) fn assert_epoch_finalizer(&self) {
+        let caller_id = env::predecessor_account_id();
+        require!(
+            Self::account_has_role(&caller_id, Role::RewardDistributor)
+                || Self::account_has_role(&caller_id, Role::GameServer),
+            format!(
+                "Caller {} does not have the RewardDistributor or GameServer role",
+                caller_id
+            )
+        );
+    }
+
+    /// Reward finalizing the open epoch of `pool_id` would lock right now:
+    /// the pool amount, capped by the pool's remaining epoch claim limit, or
+    /// nothing if no weight accrued.
+    fn internal_epoch_reward(&self, pool_id: u32) -> u128 {
+        let epoch = self.internal_current_distribution_epoch(pool_id);
+        let has_weight = self
+            .epoch_distributions
+            .get(&(pool_id, epoch))
+            .is_some_and(|distribution| distribution.total_weight.0 > 0);
+        if !has_weight {
+            return 0;
+        }
+        let amount = self.internal_pool(pool_id).amount.0;
+        self.internal_pool_epoch_remaining(pool_id)
+            .map_or(amount, |remaining| amount.min(remaining))
+    }
+
+    pub(crate) fn internal_current_distribution_epoch(&self, pool_id: u32) -> u64 {
+        self.current_distribution_epochs
+            .get(&pool_id)
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Add `weight` for `account_id` to the open epoch of `pool_id` and
+    /// return that epoch.
+    pub(crate) fn internal_accrue_weight(
+        &mut self,
+        pool_id: u32,
+        account_id: &AccountId,
+        weight: u128,
+    ) -> u64 {
+        let epoch = self.internal_current_distribution_epoch(pool_id);
+        let key = (pool_id, epoch, account_id.clone());
+        let held = self.epoch_weights.get(&key).copied().unwrap_or(0);
+        self.epoch_weights
+            .insert(key, held.checked_add(weight).expect("Weight overflow"));
+
+        let distribution = self
+            .epoch_distributions
+            .entry((pool_id, epoch))
+            .or_insert_with(|| EpochDistribution {
+                pool_id,
+                epoch,
+                ..Default::default()
+            });
+        distribution.total_weight = U128(
+            distribution
+                .total_weight
+                .0
+                .checked_add(weight)
+                .expect("Weight overflow"),
+        );
+        epoch
+    }
+
+    /// Lock the reward for the open epoch of `pool_id`, fix its reward per
+    /// share and open the next epoch. Returns the finalized epoch.
+    pub(crate) fn internal_finalize_epoch(&mut self, pool_id: u32) -> u64 {
+        self.internal_refill_before_claim(pool_id);
+
+        let epoch = self.internal_current_distribution_epoch(pool_id);
+        let mut distribution = self
+            .epoch_distributions
+            .get(&(pool_id, epoch))
+            .cloned()
+            .unwrap_or(EpochDistribution {
+                pool_id,
+                epoch,
+                ..Default::default()
+            });
+        let total_weight = distribution.total_weight.0;
+        let reward = self.internal_epoch_reward(pool_id);
+        if reward > 0 {
+            self.internal_record_pool_epoch_claim(pool_id, reward);
+            self.internal_withdraw_from_pool(pool_id, reward);
+            self.distribution_escrow = U128(
+                self.distribution_escrow
+                    .0
+                    .checked_add(reward)
+                    .expect("Distribution escrow overflow"),
+            );
+            distribution.reward_per_share = U128(
+                reward
+                    .checked_mul(REWARD_PER_SHARE_PRECISION)
+                    .expect("Reward per share overflow")
+                    / total_weight,
+            );
+        }
+        distribution.reward = U128(reward);
+        distribution.finalized_at = Some(U64(env::block_timestamp()));
+        self.epoch_distributions
+            .insert((pool_id, epoch), distribution.clone());
+        self.current_distribution_epochs
+            .insert(pool_id, epoch.checked_add(1).expect("Epoch overflow"));
+
+        ContractEvent::EpochFinalized {
+            pool_id,
+            epoch,
+            total_weight: distribution.total_weight,
+            reward: distribution.reward,
+        }
+        .emit();
+        epoch
+    }
+
+    /// Pay `account_id` its share of a finalized epoch and return the amount.
+    /// Once every unit of weight has claimed, the rounding dust goes back to
+    /// the pool.
+    pub(crate) fn internal_claim_epoch_rewards(
+        &mut self,
+        pool_id: u32,
+        epoch: u64,
+        account_id: &AccountId,
+    ) -> u128 {
+        let distribution = self
+            .epoch_distributions
+            .get(&(pool_id, epoch))
+            .filter(|distribution| distribution.finalized_at.is_some())
+            .cloned()
+            .unwrap_or_else(|| {
+                env::panic_str(&format!(
+                    "Epoch {} of pool {} has not been finalized",
+                    epoch, pool_id
+                ))
+            });
+        require!(
+            distribution.closed_at.is_none(),
+            format!(
+                "The claim window for epoch {} of pool {} has closed",
+                epoch, pool_id
+            )
+        );
+        let weight = self
+            .epoch_weights
+            .remove(&(pool_id, epoch, account_id.clone()))
+            .unwrap_or_else(|| {
+                env::panic_str(&format!(
+                    "{} has no unclaimed weight in epoch {} of pool {}",
+                    account_id, epoch, pool_id
+                ))
+            });
+        let amount = distribution.share_of(weight);
+        let claimed = distribution.claimed.0 + amount;
+        let claimed_weight = distribution.claimed_weight.0 + weight;
+        if let Some(distribution) = self.epoch_distributions.get_mut(&(pool_id, epoch)) {
+            distribution.claimed = U128(claimed);
+            distribution.claimed_weight = U128(claimed_weight);
+        }
+
+        let mut unlocked = amount;
+        let dust = if claimed_weight == distribution.total_weight.0 {
+            distribution.reward.0 - claimed
+        } else {
+            0
+        };
+        if dust > 0 {
+            unlocked += dust;
+            self.internal_return_to_pool(pool_id, dust);
+            ContractEvent::EpochDustReturned {
+                pool_id,
+                epoch,
+                amount: U128(dust),
+            }
+            .emit();
+        }
+        self.internal_unlock_distribution_escrow(unlocked);
+
+        if amount > 0 {
+            self.internal_register_with_deposit(account_id);
+            self.internal_pay_from_escrow(
+                account_id,
+                amount,
+                format!("Epoch {} rewards from pool_id: {}", epoch, pool_id),
+            );
+            self.internal_record_claim(account_id, pool_id, amount, ClaimSource::EpochDistribution);
+        }
+
+        ContractEvent::EpochRewardsClaimed {
+            pool_id,
+            epoch,
+            account_id: account_id.clone(),
+            weight: U128(weight),
+            amount: U128(amount),
+        }
+        .emit();
+        amount
+    }
+
+    /// Return what a finalized epoch has not paid out to the pool once its
+    /// claim window is over, and return that amount.
+    fn internal_close_epoch_distribution(&mut self, pool_id: u32, epoch: u64) -> u128 {
+        let distribution = self
+            .epoch_distributions
+            .get(&(pool_id, epoch))
+            .filter(|distribution| distribution.finalized_at.is_some())
+            .cloned()
+            .unwrap_or_else(|| {
+                env::panic_str(&format!(
+                    "Epoch {} of pool {} has not been finalized",
+                    epoch, pool_id
+                ))
+            });
+        require!(
+            distribution.closed_at.is_none(),
+            format!(
+                "The claim window for epoch {} of pool {} has already closed",
+                epoch, pool_id
+            )
+        );
+        let closes_at_epoch = epoch
+            .checked_add(EPOCH_CLAIM_WINDOW + 1)
+            .expect("Epoch overflow");
+        require!(
+            self.internal_current_distribution_epoch(pool_id) >= closes_at_epoch,
+            format!(
+                "Epoch {} of pool {} can be claimed until epoch {} opens",
+                epoch, pool_id, closes_at_epoch
+            )
+        );
+
+        let returned = distribution.reward.0 - distribution.claimed.0;
+        if let Some(distribution) = self.epoch_distributions.get_mut(&(pool_id, epoch)) {
+            distribution.closed_at = Some(U64(env::block_timestamp()));
+        }
+        self.internal_unlock_distribution_escrow(returned);
+        self.internal_return_to_pool(pool_id, returned);
+
+        ContractEvent::EpochDistributionClosed {
+            pool_id,
+            epoch,
+            returned: U128(returned),
+        }
+        .emit();
+        returned
+    }
+
+    fn internal_unlock_distribution_escrow(&mut self, amount: u128) {
+        self.distribution_escrow = U128(
+            self.distribution_escrow
+                .0
+                .checked_sub(amount)
+                .expect("Distribution escrow underflow"),
+        );
+    }
+
+    /// Credit `amount` released from an epoch back to the pool, unless the
+    /// pool has been closed since.
+    fn internal_return_to_pool(&mut self, pool_id: u32, amount: u128) {
+        if let Some(pool) = self.pools.get_mut(&pool_id).filter(|pool| !pool.closed) {
+            pool.amount = U128(
+                pool.amount
+                    .0
+                    .checked_add(amount)
+                    .expect("Pool amount overflow"),
+            );
+        }
+    }
+}
@@ -0,0 +1,121 @@
+use std::collections::HashSet;
+
+use near_contract_standards::storage_management::StorageManagement;
+use near_sdk::json_types::U128;
+use near_sdk::{env, log, near, require, AccountId};
+
+use crate::history::ClaimSource;
+use crate::pause::PausableFeature;
+use crate::roles::Role;
+use crate::{assert_one_yocto, Contract, ContractExt};
+
+/// Most recipients a single `claim_rewards_batch` call will pay.
+const MAX_CLAIMS_PER_BATCH: usize = 100;
+
+/// Outcome of one entry in a reward batch.
+#[near(serializers = [json])]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ClaimStatus {
+    Paid,
+    /// The recipient has no storage deposit; nothing was paid.
+    NotRegistered,
+    /// The amount was zero; nothing was paid.
+    InvalidAmount,
+    /// Rewards cannot be paid to the contract account itself.
+    InvalidRecipient,
+    /// The amount is above the multisig threshold and must go through `propose_action`.
+    RequiresMultisig,
+    /// The amount would exceed one of the pool's claim limits.
+    LimitExceeded,
+    /// The recipient already has an earlier entry in the batch; nothing was paid.
+    DuplicateRecipient,
+}
+
+#[near(serializers = [json])]
+pub struct ClaimResult {
+    pub account_id: AccountId,
+    pub amount: U128,
+    pub status: ClaimStatus,
+}
+
+#[near]
+impl Contract {
+    /// Pay rewards from one pool to many accounts in a single call. Entries
+    /// that cannot be paid are skipped and reported instead of aborting the
+    /// batch; the pool is only checked against the total of the payable
+    /// entries. Each recipient may appear once, so a large amount cannot be
+    /// split across entries to stay under the multisig threshold. Returns one
+    /// result per entry, in input order.
+    #[payable]
+    pub fn claim_rewards_batch(
+        &mut self,
+        pool_id: u32,
+        claims: Vec<(AccountId, U128)>,
+    ) -> Vec<ClaimResult> {
+        assert_one_yocto();
+        self.assert_role(Role::RewardDistributor);
+        self.assert_not_paused(PausableFeature::ClaimRewards);
+        require!(
+            claims.len() <= MAX_CLAIMS_PER_BATCH,
+            format!(
+                "At most {} claims are allowed per batch",
+                MAX_CLAIMS_PER_BATCH
+            )
+        );
+
+        self.internal_refill_before_claim(pool_id);
+        let contract_id = env::current_account_id();
+        let mut results = Vec::with_capacity(claims.len());
+        let mut payouts = Vec::new();
+        let mut total: u128 = 0;
+        let mut recipients = HashSet::with_capacity(claims.len());
+        for (account_id, amount) in claims {
+            let status = if !recipients.insert(account_id.clone()) {
+                ClaimStatus::DuplicateRecipient
+            } else if amount.0 == 0 {
+                ClaimStatus::InvalidAmount
+            } else if account_id == contract_id {
+                ClaimStatus::InvalidRecipient
+            } else if self.multisig_claim_requires_approval(amount.0) {
+                ClaimStatus::RequiresMultisig
+            } else if self.token.storage_balance_of(account_id.clone()).is_none() {
+                ClaimStatus::NotRegistered
+            } else if self
+                .internal_try_consume_claim_allowance(pool_id, &account_id, amount.0)
+                .is_err()
+            {
+                ClaimStatus::LimitExceeded
+            } else {
+                total = total.checked_add(amount.0).expect("Batch total overflow");
+                payouts.push((account_id.clone(), amount.0));
+                ClaimStatus::Paid
+            };
+            results.push(ClaimResult {
+                account_id,
+                amount,
+                status,
+            });
+        }
+
+        if total > 0 {
+            self.internal_withdraw_from_pool(pool_id, total);
+            self.internal_pay_many_from_escrow(
+                &payouts,
+                &format!("Reward claim from pool_id: {}", pool_id),
+            );
+            for (account_id, amount) in &payouts {
+                self.internal_record_claim(account_id, pool_id, *amount, ClaimSource::Batch);
+            }
+        }
+
+        log!(
+            "{} tokens paid to {} of {} accounts from Pool ID: {} by {}",
+            total,
+            payouts.len(),
+            results.len(),
+            pool_id,
+            env::predecessor_account_id()
+        );
+        results
+    }
+}
@@ -0,0 +1,260 @@
+use near_contract_standards::fungible_token::metadata::FungibleTokenMetadata;
+use near_sdk::json_types::{Base58CryptoHash, U128, U64};
+use near_sdk::{near, AccountId};
+
+use crate::multisig::MultisigAction;
+use crate::pause::PausableFeature;
+use crate::pools::{PoolKind, RefillPolicy};
+use crate::raffle::RaffleWinner;
+use crate::roles::Role;
+use crate::tapping::RemainderPolicy;
+use crate::timelock::TimelockAction;
+use crate::EmissionConfig;
+
+/// NEP-297 events emitted by the PTB contract.
+#[near(event_json(standard = "ptb"))]
+pub enum ContractEvent {
+    #[event_version("1.0.0")]
+    EmissionConfigUpdated {
+        updated_by: AccountId,
+        old_config: EmissionConfig,
+        new_config: EmissionConfig,
+    },
+    #[event_version("1.0.0")]
+    MetadataUpdated {
+        updated_by: AccountId,
+        metadata: FungibleTokenMetadata,
+    },
+    #[event_version("1.0.0")]
+    PoolCreated {
+        pool_id: u32,
+        kind: PoolKind,
+        refill_policy: RefillPolicy,
+    },
+    #[event_version("1.0.0")]
+    PoolFunded {
+        pool_id: u32,
+        funded_by: AccountId,
+        amount: U128,
+    },
+    #[event_version("1.0.0")]
+    PoolRefillPolicyUpdated {
+        pool_id: u32,
+        refill_policy: RefillPolicy,
+    },
+    #[event_version("1.0.0")]
+    PoolClosed {
+        pool_id: u32,
+        remaining_amount: U128,
+    },
+    #[event_version("1.0.0")]
+    RoleGranted {
+        account_id: AccountId,
+        role: Role,
+        granted_by: AccountId,
+    },
+    #[event_version("1.0.0")]
+    RoleRevoked {
+        account_id: AccountId,
+        role: Role,
+        revoked_by: AccountId,
+    },
+    #[event_version("1.0.0")]
+    FeaturePaused {
+        feature: PausableFeature,
+        paused_by: AccountId,
+    },
+    #[event_version("1.0.0")]
+    FeatureUnpaused {
+        feature: PausableFeature,
+        unpaused_by: AccountId,
+    },
+    #[event_version("1.0.0")]
+    OwnershipTransferProposed {
+        owner_id: AccountId,
+        proposed_owner: AccountId,
+        expires_at: U64,
+    },
+    #[event_version("1.0.0")]
+    OwnershipTransferAccepted {
+        previous_owner: AccountId,
+        new_owner: AccountId,
+    },
+    #[event_version("1.0.0")]
+    OwnershipTransferCancelled {
+        owner_id: AccountId,
+        proposed_owner: AccountId,
+    },
+    #[event_version("1.0.0")]
+    OwnershipRenounced { previous_owner: AccountId },
+    #[event_version("1.0.0")]
+    TimelockActionQueued {
+        action_id: u64,
+        action: TimelockAction,
+        proposed_by: AccountId,
+        eta: U64,
+    },
+    #[event_version("1.0.0")]
+    TimelockActionExecuted {
+        action_id: u64,
+        executed_by: AccountId,
+    },
+    #[event_version("1.0.0")]
+    TimelockActionCancelled {
+        action_id: u64,
+        cancelled_by: AccountId,
+    },
+    #[event_version("1.0.0")]
+    TimelockActionExpired { action_id: u64, expired_at: U64 },
+    #[event_version("1.0.0")]
+    MultisigActionProposed {
+        proposal_id: u64,
+        action: MultisigAction,
+        proposed_by: AccountId,
+        expires_at: U64,
+    },
+    #[event_version("1.0.0")]
+    MultisigActionApproved {
+        proposal_id: u64,
+        approved_by: AccountId,
+    },
+    #[event_version("1.0.0")]
+    MultisigActionExecuted {
+        proposal_id: u64,
+        executed_by: AccountId,
+    },
+    #[event_version("1.0.0")]
+    MultisigActionCancelled {
+        proposal_id: u64,
+        cancelled_by: AccountId,
+    },
+    #[event_version("1.0.0")]
+    ClaimRootSet {
+        pool_id: u32,
+        epoch: u32,
+        root: Base58CryptoHash,
+        total: U128,
+        set_by: AccountId,
+    },
+    #[event_version("1.0.0")]
+    RewardClaimedWithProof {
+        pool_id: u32,
+        epoch: u32,
+        index: u32,
+        account_id: AccountId,
+        amount: U128,
+    },
+    #[event_version("1.0.0")]
+    VoucherRedeemed {
+        pool_id: u32,
+        recipient: AccountId,
+        amount: U128,
+        nonce: U64,
+    },
+    #[event_version("1.0.0")]
+    TappingPoolRefilled {
+        pool_id: u32,
+        period: U64,
+        amount: U128,
+        remainder: U128,
+        remainder_policy: RemainderPolicy,
+    },
+    #[event_version("1.0.0")]
+    RaffleRoundCreated {
+        round_id: u64,
+        ticket_price: U128,
+        prizes: Vec<U128>,
+        close_height: U64,
+    },
+    #[event_version("1.0.0")]
+    RaffleEntered {
+        round_id: u64,
+        account_id: AccountId,
+        first_ticket: U64,
+        tickets: U64,
+    },
+    #[event_version("1.0.0")]
+    RaffleDrawn {
+        round_id: u64,
+        /// Hex-encoded block random seed the winners were drawn from.
+        random_seed: String,
+        winners: Vec<RaffleWinner>,
+    },
+    #[event_version("1.0.0")]
+    RaffleSecretCommitted {
+        round_id: u64,
+        commitment: Base58CryptoHash,
+        reveal_deadline: U64,
+    },
+    #[event_version("1.0.0")]
+    RaffleSecretRevealed {
+        round_id: u64,
+        /// Hex-encoded revealed secret.
+        secret: String,
+        /// Hex-encoded block random seed it was mixed with.
+        block_seed: String,
+    },
+    #[event_version("1.0.0")]
+    RaffleExpired {
+        round_id: u64,
+        returned_prizes: U128,
+    },
+    #[event_version("1.0.0")]
+    RaffleRefunded {
+        round_id: u64,
+        account_id: AccountId,
+        amount: U128,
+    },
+    #[event_version("1.0.0")]
+    TapsSubmitted {
+        epoch: u64,
+        submitted_by: AccountId,
+        accounts: u32,
+        taps: U64,
+    },
+    #[event_version("1.0.0")]
+    EpochFinalized {
+        pool_id: u32,
+        epoch: u64,
+        total_weight: U128,
+        reward: U128,
+    },
+    #[event_version("1.0.0")]
+    EpochRewardsClaimed {
+        pool_id: u32,
+        epoch: u64,
+        account_id: AccountId,
+        weight: U128,
+        amount: U128,
+    },
+    #[event_version("1.0.0")]
+    EpochDustReturned {
+        pool_id: u32,
+        epoch: u64,
+        amount: U128,
+    },
+    #[event_version("1.0.0")]
+    EpochDistributionClosed {
+        pool_id: u32,
+        epoch: u64,
+        returned: U128,
+    },
+    #[event_version("1.0.0")]
+    Staked { account_id: AccountId, amount: U128 },
+    #[event_version("1.0.0")]
+    Unstaked {
+        account_id: AccountId,
+        amount: U128,
+        /// When the tokens can be withdrawn (nanoseconds).
+        available_at: U64,
+    },
+    #[event_version("1.0.0")]
+    UnstakeWithdrawn { account_id: AccountId, amount: U128 },
+    #[event_version("1.0.0")]
+    StakingRewardsClaimed { account_id: AccountId, amount: U128 },
+    #[event_version("1.0.0")]
+    StakingRewardsFunded {
+        amount: U128,
+        reward_per_token: U128,
+    },
+}
@@ -0,0 +1,143 @@
+use near_sdk::json_types::{U128, U64};
+use near_sdk::{env, near, AccountId};
+
+use crate::{Contract, ContractExt};
+
+/// Number of claims kept in the recent-claims ring buffer.
+const RECENT_CLAIMS_CAPACITY: u64 = 256;
+
+/// How a reward reached the user.
+#[near(serializers = [borsh, json])]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ClaimSource {
+    /// `claim_rewards`, directly or through a multisig proposal.
+    Distributor,
+    Batch,
+    MerkleProof,
+    Voucher,
+    Raffle,
+    /// `claim_epoch_rewards` from a finalized distribution epoch.
+    EpochDistribution,
+}
+
+#[near(serializers = [borsh, json])]
+#[derive(Clone)]
+pub struct PoolClaimTotal {
+    pub pool_id: u32,
+    pub total_claimed: U128,
+}
+
+/// Lifetime reward totals for one account.
+#[near(serializers = [borsh, json])]
+#[derive(Clone, Default)]
+pub struct UserRewardStats {
+    pub total_claimed: U128,
+    pub per_pool: Vec<PoolClaimTotal>,
+    pub claim_count: u64,
+    /// Time (nanoseconds) of the latest claim, 0 if there was none.
+    pub last_claim_at: U64,
+}
+
+#[near(serializers = [borsh, json])]
+#[derive(Clone)]
+pub struct ClaimRecord {
+    /// Position of the claim in the contract's claim history, starting at 0.
+    pub seq: U64,
+    pub account_id: AccountId,
+    pub pool_id: u32,
+    pub amount: U128,
+    pub source: ClaimSource,
+    pub timestamp: U64,
+    pub block_height: U64,
+}
+
+#[near]
+impl Contract {
+    /// Lifetime reward totals for `account_id`
+    pub fn get_user_rewards(&self, account_id: AccountId) -> UserRewardStats {
+        self.user_reward_stats
+            .get(&account_id)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Most recent claims, newest first, optionally only those paid to
+    /// `account_id`. Only the last 256 claims are retained.
+    pub fn get_recent_claims(
+        &self,
+        account_id: Option<AccountId>,
+        limit: Option<u32>,
+    ) -> Vec<ClaimRecord> {
+        let limit = limit
+            .map_or(RECENT_CLAIMS_CAPACITY, u64::from)
+            .min(RECENT_CLAIMS_CAPACITY) as usize;
+        let oldest = self.next_claim_seq.saturating_sub(RECENT_CLAIMS_CAPACITY);
+        (oldest..self.next_claim_seq)
+            .rev()
+            .filter_map(|seq| self.recent_claims.get(&(seq % RECENT_CLAIMS_CAPACITY)))
+            .filter(|record| {
+                account_id
+                    .as_ref()
+                    .is_none_or(|id| record.account_id == *id)
+            })
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+}
+
+impl Contract {
+    /// Record a reward payout in the receiver's stats and the recent-claims index.
+    pub(crate) fn internal_record_claim(
+        &mut self,
+        account_id: &AccountId,
+        pool_id: u32,
+        amount: u128,
+        source: ClaimSource,
+    ) {
+        let now = env::block_timestamp();
+        let stats = self
+            .user_reward_stats
+            .entry(account_id.clone())
+            .or_default();
+        stats.total_claimed = U128(
+            stats
+                .total_claimed
+                .0
+                .checked_add(amount)
+                .expect("Claim total overflow"),
+        );
+        match stats.per_pool.iter_mut().find(|t| t.pool_id == pool_id) {
+            Some(total) => {
+                total.total_claimed = U128(
+                    total
+                        .total_claimed
+                        .0
+                        .checked_add(amount)
+                        .expect("Claim total overflow"),
+                )
+            }
+            None => stats.per_pool.push(PoolClaimTotal {
+                pool_id,
+                total_claimed: U128(amount),
+            }),
+        }
+        stats.claim_count += 1;
+        stats.last_claim_at = U64(now);
+
+        let seq = self.next_claim_seq;
+        self.next_claim_seq += 1;
+        self.recent_claims.insert(
+            seq % RECENT_CLAIMS_CAPACITY,
+            ClaimRecord {
+                seq: U64(seq),
+                account_id: account_id.clone(),
+                pool_id,
+                amount: U128(amount),
+                source,
+                timestamp: U64(now),
+                block_height: U64(env::block_height()),
+            },
+        );
+    }
+}
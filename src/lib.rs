@@ -7,49 +7,308 @@ use near_contract_standards::fungible_token::{
 use near_contract_standards::storage_management::{
     StorageBalance, StorageBalanceBounds, StorageManagement,
 };
-use near_sdk::borsh::BorshDeserialize;
 use near_sdk::borsh::BorshSerialize;
 use near_sdk::collections::LazyOption;
-use near_sdk::collections::LookupMap;
+use near_sdk::collections::LookupSet;
+use near_sdk::store::{IterableMap, LookupMap};
 use near_sdk::json_types::{U128, U64};
 use near_sdk::{
     env, log, near, require, AccountId, BorshStorageKey, NearToken, PanicOnDefault, PromiseOrValue,
+    PublicKey,
 };
 
-#[derive(BorshSerialize, BorshDeserialize, PanicOnDefault)]
+pub mod accrual;
+pub mod claims;
+pub mod events;
+pub mod history;
+pub mod limits;
+pub mod merkle;
+mod migrate;
+pub mod multisig;
+pub mod pause;
+pub mod pools;
+pub mod raffle;
+pub mod roles;
+pub mod staking;
+pub mod tapping;
+pub mod taps;
+pub mod timelock;
+pub mod vouchers;
+
+use accrual::EpochDistribution;
+use events::ContractEvent;
+use history::{ClaimRecord, ClaimSource, UserRewardStats};
+use limits::{ClaimLimits, EpochUsage};
+use merkle::ClaimRoot;
+use multisig::{MultisigConfig, MultisigProposal};
+use near_sdk_contract_tools::Rbac;
+use pause::PausableFeature;
+use pools::{
+    PoolKind, RefillPolicy, RewardPool, GLOBAL_TAPPING_POOL_ID, LOOT_RAFFLE_POOL_ID,
+};
+use raffle::{RaffleEntry, RaffleRound};
+use roles::Role;
+use staking::{StakeAccount, StakingConfig, StakingState};
+use tapping::TappingRefillSchedule;
+use timelock::{QueuedAction, TimelockAction, DEFAULT_TIMELOCK_DELAY_NS};
+
+/// Monthly decay expressed as an exact `numerator / denominator` ratio.
+///
+/// Applying the factor multiplies with checked `u128` math and always rounds
+/// down, so every decayed amount can be reproduced off-chain to the base unit.
+#[near(serializers = [borsh, json])]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct DecayFactor {
+    pub numerator: U128,
+    pub denominator: U128,
+}
+
+impl DecayFactor {
+    /// Returns `floor(value * numerator / denominator)`.
+    pub fn apply(&self, value: u128) -> u128 {
+        require!(self.denominator.0 > 0, "Decay factor denominator must be non-zero");
+        value
+            .checked_mul(self.numerator.0)
+            .expect("Decay multiplication overflow")
+            / self.denominator.0
+    }
+}
+
+/// Tokenomics parameters for a deployment.
+///
+/// Amounts are in whole tokens for `initial_emissions` and in base units for
+/// the pool amounts, matching how they were hard-coded before.
+#[near(serializers = [borsh, json])]
+#[derive(Clone, PartialEq, Debug)]
+pub struct EmissionConfig {
+    /// Emissions for month 0, in whole tokens.
+    pub initial_emissions: U64,
+    /// Decay applied to emissions and the loot raffle pool on every mint after month 0.
+    pub decay_factor: DecayFactor,
+    /// Initial loot raffle pool amount, in base units.
+    pub raffle_seed: U128,
+    /// Initial amount and reset amount of the global tapping pool, in base units.
+    pub tapping_reset: U128,
+}
+
+impl Default for EmissionConfig {
+    fn default() -> Self {
+        Self {
+            initial_emissions: U64(3_000_000_000),
+            decay_factor: DEFAULT_DECAY_FACTOR,
+            raffle_seed: U128(50_000_000 * TOKEN_UNIT),
+            tapping_reset: U128(1_000_000_000 * TOKEN_UNIT),
+        }
+    }
+}
+
+impl EmissionConfig {
+    fn assert_valid(&self) {
+        require!(
+            self.initial_emissions.0 > 0,
+            "Initial emissions must be greater than zero"
+        );
+        require!(
+            self.decay_factor.denominator.0 > 0,
+            "Decay factor denominator must be non-zero"
+        );
+        require!(
+            self.decay_factor.numerator.0 <= self.decay_factor.denominator.0,
+            "Decay factor must not exceed 1"
+        );
+    }
+
+    /// `initial_emissions` and `raffle_seed` only seed state created in
+    /// `new_default_meta`, so an update must keep them as they are.
+    fn assert_valid_update(&self, current: &EmissionConfig) {
+        self.assert_valid();
+        require!(
+            self.initial_emissions == current.initial_emissions,
+            "Initial emissions cannot be changed after initialization"
+        );
+        require!(
+            self.raffle_seed == current.raffle_seed,
+            "Raffle seed cannot be changed after initialization"
+        );
+    }
+}
+
+#[near(serializers = [borsh, json])]
+#[derive(PanicOnDefault, Clone)]
 pub struct EmissionsAccount {
     pub initial_emissions: U64,
-    pub decay_factor: f64,
     pub current_month: u32,
     pub current_emissions: U64,
+    /// Base units minted by all epochs so far.
+    pub total_minted: U128,
+    /// Start of the most recently minted epoch (nanoseconds). Before the
+    /// first mint this is the start of month 0.
     pub last_mint_timestamp: U64,
 }
 
-#[derive(BorshSerialize, BorshDeserialize, PanicOnDefault)]
-pub struct RafflePool {
-    pub pool_id: u32,
-    pub amount: U128,
-    pub total_amount: U128,
+impl EmissionsAccount {
+    /// Fresh schedule whose month 0 starts at `timestamp` (nanoseconds).
+    pub fn new(config: &EmissionConfig, timestamp: u64) -> Self {
+        Self {
+            initial_emissions: config.initial_emissions,
+            current_month: 0,
+            current_emissions: config.initial_emissions,
+            total_minted: U128(0),
+            last_mint_timestamp: U64(timestamp),
+        }
+    }
+
+    /// Timestamp (nanoseconds) from which the next epoch can be minted.
+    pub fn next_mint_timestamp(&self) -> u64 {
+        if self.current_month == 0 {
+            self.last_mint_timestamp.0
+        } else {
+            self.last_mint_timestamp
+                .0
+                .checked_add(EMISSION_EPOCH_NS)
+                .expect("Next mint timestamp overflow")
+        }
+    }
+
+    /// Number of epochs that have come due by `timestamp` and not been minted.
+    pub fn pending_epochs(&self, timestamp: u64) -> u64 {
+        let next_mint_timestamp = self.next_mint_timestamp();
+        if timestamp < next_mint_timestamp {
+            return 0;
+        }
+        (timestamp - next_mint_timestamp) / EMISSION_EPOCH_NS + 1
+    }
+
+    /// Move the schedule forward by one epoch and return the amount it mints,
+    /// in base units. Emissions decay on every epoch after month 0.
+    pub fn advance_epoch(&mut self, decay_factor: &DecayFactor) -> u128 {
+        if self.current_month > 0 {
+            let decayed = decay_factor.apply(u128::from(self.current_emissions.0));
+            self.current_emissions =
+                U64(u64::try_from(decayed).expect("Decayed emissions overflow"));
+        }
+        self.last_mint_timestamp = U64(self.next_mint_timestamp());
+        self.current_month = self
+            .current_month
+            .checked_add(1)
+            .expect("Current month addition overflow");
+
+        let mint_amount = u128::from(self.current_emissions.0)
+            .checked_mul(TOKEN_UNIT)
+            .expect("Mint amount multiplication overflow");
+        self.total_minted = U128(
+            self.total_minted
+                .0
+                .checked_add(mint_amount)
+                .expect("Total minted addition overflow"),
+        );
+        mint_amount
+    }
 }
 
-#[derive(BorshSerialize, BorshDeserialize)]
-pub struct TappingPool {
-    pub pool_id: u32,
+/// Projected mint for one emission month
+#[near(serializers = [json])]
+pub struct EmissionForecast {
+    pub month: u32,
+    /// Earliest time (nanoseconds) at which the month can be minted.
+    pub timestamp: U64,
     pub amount: U128,
 }
 
-#[derive(PanicOnDefault)]
+/// Ownership transfer waiting for the proposed owner to accept it
+#[near(serializers = [borsh, json])]
+#[derive(Clone)]
+pub struct PendingOwnershipTransfer {
+    pub proposed_owner: AccountId,
+    pub proposed_at: U64,
+    pub expires_at: U64,
+}
+
+#[derive(PanicOnDefault, Rbac)]
+#[rbac(roles = "Role")]
 #[near(contract_state)]
 pub struct Contract {
     token: FungibleToken,
     metadata: LazyOption<FungibleTokenMetadata>,
-    emissions_account: LookupMap<AccountId, EmissionsAccount>,
-    loot_raffle_pool: LookupMap<u32, RafflePool>,
-    global_tapping_pool: LookupMap<u32, TappingPool>,
+    /// Contract-wide emissions schedule, independent of who owns the contract.
+    emissions: EmissionsAccount,
+    pools: IterableMap<u32, RewardPool>,
+    next_pool_id: u32,
+    paused_features: LookupSet<PausableFeature>,
+    emission_config: EmissionConfig,
+    max_supply: U128,
     owner_id: AccountId,
-    proposed_owner: Option<AccountId>, //field for proposed owner
+    pending_ownership_transfer: Option<PendingOwnershipTransfer>,
+    /// Delay (nanoseconds) between queueing a privileged action and executing it.
+    timelock_delay: U64,
+    queued_actions: IterableMap<u64, QueuedAction>,
+    next_action_id: u64,
+    multisig_config: MultisigConfig,
+    multisig_proposals: IterableMap<u64, MultisigProposal>,
+    next_proposal_id: u64,
+    claim_roots: LookupMap<(u32, u32), ClaimRoot>,
+    /// Claimed leaves per `(pool_id, epoch, index / 64)`, one bit per leaf.
+    claimed_bitmap: LookupMap<(u32, u32, u32), u64>,
+    /// Root totals reserved out of their pools and not yet claimed.
+    claim_root_escrow: U128,
+    /// ed25519 key reward vouchers must be signed with; `None` disables them.
+    voucher_signer: Option<PublicKey>,
+    /// ed25519 key tap reports must be signed with; `None` disables them.
+    tap_signer: Option<PublicKey>,
+    used_voucher_nonces: LookupSet<u64>,
+    user_reward_stats: LookupMap<AccountId, UserRewardStats>,
+    /// Ring buffer of the latest claims, keyed by `seq % capacity`.
+    recent_claims: LookupMap<u64, ClaimRecord>,
+    next_claim_seq: u64,
+    pool_claim_limits: LookupMap<u32, ClaimLimits>,
+    pool_epoch_claims: LookupMap<u32, EpochUsage>,
+    user_epoch_claims: LookupMap<(u32, AccountId), EpochUsage>,
+    /// Refill schedule for the global tapping pool; `None` refills it on `mint`.
+    tapping_refill: Option<TappingRefillSchedule>,
+    raffle_rounds: IterableMap<u64, RaffleRound>,
+    next_raffle_round_id: u64,
+    /// Entries per `(round_id, entry_index)`, in ascending ticket order.
+    raffle_entries: LookupMap<(u64, u32), RaffleEntry>,
+    raffle_tickets: LookupMap<(u64, AccountId), u64>,
+    /// Prizes and ticket payments held in the contract account for rounds
+    /// that have not been drawn.
+    raffle_escrow: U128,
+    /// Epoch currently accruing weight per pool; absent means epoch 0.
+    current_distribution_epochs: LookupMap<u32, u64>,
+    epoch_distributions: LookupMap<(u32, u64), EpochDistribution>,
+    /// Unclaimed weight per `(pool_id, epoch, account_id)`.
+    epoch_weights: LookupMap<(u32, u64, AccountId), u128>,
+    /// Rewards locked for finalized distribution epochs and not yet claimed.
+    distribution_escrow: U128,
+    staking_config: StakingConfig,
+    staking: StakingState,
+    stakes: LookupMap<AccountId, StakeAccount>,
+    /// Set once `renounce_ownership` executes; ownership can never be taken up again.
+    ownership_renounced: bool,
 }
 
+/// One whole PUSH token in base units (5 decimals).
+const TOKEN_UNIT: u128 = 100_000;
+
+/// Default monthly decay of 0.8705505633, stored as an exact ratio.
+const DEFAULT_DECAY_FACTOR: DecayFactor = DecayFactor {
+    numerator: U128(8_705_505_633),
+    denominator: U128(10_000_000_000),
+};
+
+/// Length of one emission epoch: 30 days, in nanoseconds.
+pub(crate) const EMISSION_EPOCH_NS: u64 = 30 * 24 * 60 * 60 * 1_000_000_000;
+
+/// Epochs minted by a single `mint` call when no bound is given.
+const DEFAULT_MAX_EPOCHS_PER_MINT: u32 = 12;
+
+/// How long a proposed owner has to accept ownership: 7 days, in nanoseconds.
+const OWNERSHIP_TRANSFER_EXPIRY_NS: u64 = 7 * 24 * 60 * 60 * 1_000_000_000;
+
+/// Most months `get_emission_schedule` returns per call, and how far past the
+/// current month it and `get_cumulative_emissions` will project.
+const MAX_FORECAST_MONTHS: u32 = 240;
+
 const DATA_IMAGE_SVG_NEAR_ICON: &str = "https://red-defensive-termite-556.mypinata.cloud/ipfs/QmUCUAABBsqkhSw3HoeMtecwVAeKBmxUgj2GLwmxuNojbV";
 
 #[derive(BorshSerialize, BorshStorageKey)]
@@ -57,15 +316,42 @@ const DATA_IMAGE_SVG_NEAR_ICON: &str = "https://red-defensive-termite-556.mypina
 enum StorageKey {
     FungibleToken,
     Metadata,
+    RewardPools,
+    PausedFeatures,
+    QueuedActions,
+    MultisigProposals,
+    ClaimRoots,
+    ClaimedBitmap,
+    UsedVoucherNonces,
+    UserRewardStats,
+    RecentClaims,
+    PoolClaimLimits,
+    PoolEpochClaims,
+    UserEpochClaims,
+    RaffleRounds,
+    RaffleEntries,
+    RaffleTickets,
+    CurrentDistributionEpochs,
+    EpochDistributions,
+    EpochWeights,
+    Stakes,
 }
 
 /// Initializes the contract with the given total supply
 #[near]
 impl Contract {
+    /// `max_supply` is a hard cap on `ft_total_supply` that every mint path
+    /// enforces. `emission_config` defaults to the mainnet tokenomics when omitted.
     #[init]
-    pub fn new_default_meta(total_supply: U128) -> Self {
+    pub fn new_default_meta(
+        total_supply: U128,
+        max_supply: U128,
+        emission_config: Option<EmissionConfig>,
+    ) -> Self {
         require!(!env::state_exists(), "Already initialized");
         let caller_id: AccountId = env::predecessor_account_id();
+        let emission_config = emission_config.unwrap_or_default();
+        emission_config.assert_valid();
 
         let mut this = Self {
             token: FungibleToken::new(StorageKey::FungibleToken),
@@ -81,143 +367,271 @@ impl Contract {
                     decimals: 5,
                 }),
             ),
-            emissions_account: LookupMap::new(b"e"),
-            loot_raffle_pool: LookupMap::new(b"l"),
-            global_tapping_pool: LookupMap::new(b"g"),
+            emissions: EmissionsAccount::new(&emission_config, env::block_timestamp()),
+            pools: IterableMap::new(StorageKey::RewardPools),
+            next_pool_id: GLOBAL_TAPPING_POOL_ID + 1,
+            paused_features: LookupSet::new(StorageKey::PausedFeatures),
+            emission_config,
+            max_supply,
             owner_id: caller_id.clone(),
-            proposed_owner: None,
+            pending_ownership_transfer: None,
+            timelock_delay: U64(DEFAULT_TIMELOCK_DELAY_NS),
+            queued_actions: IterableMap::new(StorageKey::QueuedActions),
+            next_action_id: 0,
+            multisig_config: MultisigConfig::default(),
+            multisig_proposals: IterableMap::new(StorageKey::MultisigProposals),
+            next_proposal_id: 0,
+            claim_roots: LookupMap::new(StorageKey::ClaimRoots),
+            claimed_bitmap: LookupMap::new(StorageKey::ClaimedBitmap),
+            claim_root_escrow: U128(0),
+            voucher_signer: None,
+            tap_signer: None,
+            used_voucher_nonces: LookupSet::new(StorageKey::UsedVoucherNonces),
+            user_reward_stats: LookupMap::new(StorageKey::UserRewardStats),
+            recent_claims: LookupMap::new(StorageKey::RecentClaims),
+            next_claim_seq: 0,
+            pool_claim_limits: LookupMap::new(StorageKey::PoolClaimLimits),
+            pool_epoch_claims: LookupMap::new(StorageKey::PoolEpochClaims),
+            user_epoch_claims: LookupMap::new(StorageKey::UserEpochClaims),
+            tapping_refill: None,
+            raffle_rounds: IterableMap::new(StorageKey::RaffleRounds),
+            next_raffle_round_id: 0,
+            raffle_entries: LookupMap::new(StorageKey::RaffleEntries),
+            raffle_tickets: LookupMap::new(StorageKey::RaffleTickets),
+            raffle_escrow: U128(0),
+            current_distribution_epochs: LookupMap::new(StorageKey::CurrentDistributionEpochs),
+            epoch_distributions: LookupMap::new(StorageKey::EpochDistributions),
+            epoch_weights: LookupMap::new(StorageKey::EpochWeights),
+            distribution_escrow: U128(0),
+            staking_config: StakingConfig::default(),
+            staking: StakingState::default(),
+            stakes: LookupMap::new(StorageKey::Stakes),
+            ownership_renounced: false,
         };
-        // Initialize Emissions Account
-        this.emissions_account.insert(
-            &this.owner_id,
-            &EmissionsAccount {
-                initial_emissions: U64(3_000_000_000), 
-               decay_factor: 0.8705505633,            
-                current_month: 0,                      
-                current_emissions: U64(3_000_000_000), 
-               last_mint_timestamp: U64(env::block_timestamp()), // Wrap timestamp in U64
-            },
-        );
-
         // Initialize Raffle and Tapping Pools
-        this.loot_raffle_pool.insert(
-            &1,
-            &RafflePool {
-                pool_id: 1,                     
-                amount: U128(50_000_000_00000), 
-                total_amount: U128(0),          
-            },
+        this.pools.insert(
+            LOOT_RAFFLE_POOL_ID,
+            RewardPool::new(
+                LOOT_RAFFLE_POOL_ID,
+                PoolKind::LootRaffle,
+                this.emission_config.raffle_seed,
+                RefillPolicy::Decay,
+            ),
         );
-
-        this.global_tapping_pool.insert(
-            &2,
-            &TappingPool {
-                pool_id: 2,                        
-                amount: U128(1_000_000_000_00000), 
-            },
+        this.pools.insert(
+            GLOBAL_TAPPING_POOL_ID,
+            RewardPool::new(
+                GLOBAL_TAPPING_POOL_ID,
+                PoolKind::GlobalTapping,
+                this.emission_config.tapping_reset,
+                RefillPolicy::Reset {
+                    amount: this.emission_config.tapping_reset,
+                },
+            ),
         );
         this.token.internal_register_account(&this.owner_id);
-        this.token
-            .internal_deposit(&this.owner_id, total_supply.into());
+        let owner_id = this.owner_id.clone();
+        this.internal_grant_all_roles(&owner_id);
+        this.internal_mint(&owner_id, total_supply.into());
+
+        // Escrow the initial pool amounts in the contract's own account, out of
+        // the initial supply
+        let initial_pool_amount = this.internal_total_pool_amount();
+        require!(
+            total_supply.0 >= initial_pool_amount,
+            format!(
+                "The total supply must cover the initial pool allocations of {}",
+                initial_pool_amount
+            )
+        );
+        let escrow_id = env::current_account_id();
+        if escrow_id != owner_id {
+            this.token.internal_register_account(&escrow_id);
+            this.token.internal_transfer(
+                &owner_id,
+                &escrow_id,
+                initial_pool_amount,
+                Some("Initial reward pool allocation".to_string()),
+            );
+        }
 
         this
     }
 
-    /// Initiate Ownership Transfer
+    /// Queue an ownership transfer behind the timelock. Once executed, the
+    /// proposed owner has 7 days to accept. Returns the queued action id.
     #[payable]
-    pub fn initiate_ownership_transfer(&mut self, new_owner: AccountId) {
+    pub fn initiate_ownership_transfer(&mut self, new_owner: AccountId) -> u64 {
         assert_one_yocto();
         require!(
             env::predecessor_account_id() == self.owner_id,
             "Only the current owner can initiate an ownership transfer."
         );
+        require!(
+            !self.ownership_renounced,
+            "Ownership has already been renounced."
+        );
         require!(
             new_owner != self.owner_id,
             "New owner cannot be the current owner."
         );
 
-        self.proposed_owner = Some(new_owner.clone());
-        log!("Ownership transfer initiated to: {}", new_owner);
+        self.internal_queue_action(TimelockAction::TransferOwnership { new_owner })
+    }
+
+    /// Cancel a pending ownership transfer, along with any transfer still
+    /// waiting in the timelock
+    #[payable]
+    pub fn cancel_ownership_transfer(&mut self) {
+        assert_one_yocto();
+        let caller_id = env::predecessor_account_id();
+        require!(
+            caller_id == self.owner_id,
+            "Only the current owner can cancel an ownership transfer."
+        );
+
+        let queued: Vec<(u64, AccountId)> = self
+            .queued_actions
+            .values()
+            .filter_map(|queued| match &queued.action {
+                TimelockAction::TransferOwnership { new_owner } => {
+                    Some((queued.action_id, new_owner.clone()))
+                }
+                _ => None,
+            })
+            .collect();
+        let pending = self.pending_ownership_transfer.take();
+        require!(
+            pending.is_some() || !queued.is_empty(),
+            "No ownership transfer initiated."
+        );
+
+        for (action_id, proposed_owner) in queued {
+            self.queued_actions.remove(&action_id);
+            ContractEvent::TimelockActionCancelled {
+                action_id,
+                cancelled_by: caller_id.clone(),
+            }
+            .emit();
+            ContractEvent::OwnershipTransferCancelled {
+                owner_id: self.owner_id.clone(),
+                proposed_owner,
+            }
+            .emit();
+        }
+        if let Some(pending) = pending {
+            ContractEvent::OwnershipTransferCancelled {
+                owner_id: self.owner_id.clone(),
+                proposed_owner: pending.proposed_owner,
+            }
+            .emit();
+        }
     }
 
     #[payable]
     /// Accept Ownership Transfer
     pub fn accept_ownership(&mut self) {
         assert_one_yocto();
-        let proposed_owner = self
-            .proposed_owner
+        let pending = self
+            .pending_ownership_transfer
             .clone()
             .expect("No ownership transfer initiated.");
         require!(
-            env::predecessor_account_id() == proposed_owner,
+            env::predecessor_account_id() == pending.proposed_owner,
             "Only the proposed owner can accept the ownership transfer."
         );
+        require!(
+            env::block_timestamp() < pending.expires_at.0,
+            "The ownership transfer proposal has expired."
+        );
 
         // Transfer ownership
-        self.owner_id = proposed_owner.clone();
-        self.proposed_owner = None;
-
-        // Ensure the new owner has an emissions account
-        if self.emissions_account.get(&self.owner_id).is_none() {
-            self.emissions_account.insert(
-                &self.owner_id,
-                &EmissionsAccount {
-                    initial_emissions: U64(3_000_000_000),
-                    decay_factor: 0.8705505633,
-                    current_month: 0,
-                    current_emissions: U64(3_000_000_000), 
-                    last_mint_timestamp: U64(env::block_timestamp()), 
-                },
-            );
-            log!(
-                "Initialized emissions account for new owner: {}",
-                self.owner_id
-            );
-        }
-
-        // Ensure the new owner has a loot raffle pool account
-        if self.loot_raffle_pool.get(&1).is_none() {
-            self.loot_raffle_pool.insert(
-                &1,
-                &RafflePool {
-                    pool_id: 1,
-                    amount: U128(50_000_000_00000), 
-                    total_amount: U128(0),
-                },
-            );
-            log!(
-                "Initialized loot raffle pool for new owner: {}",
-                self.owner_id
-            );
+        let proposed_owner = pending.proposed_owner;
+        let previous_owner = std::mem::replace(&mut self.owner_id, proposed_owner.clone());
+        self.pending_ownership_transfer = None;
+        self.internal_transfer_owner_role(&previous_owner, &proposed_owner);
+
+        ContractEvent::OwnershipTransferAccepted {
+            previous_owner,
+            new_owner: proposed_owner,
         }
+        .emit();
+    }
 
-        // Ensure the new owner has a global tapping pool account
-        if self.global_tapping_pool.get(&2).is_none() {
-            self.global_tapping_pool.insert(
-                &2,
-                &TappingPool {
-                    pool_id: 2,
-                    amount: U128(1_000_000_000_00000), // Wrap integer in U128
-                },
-            );
-            log!(
-                "Initialized global tapping pool for new owner: {}",
-                self.owner_id
-            );
-        }
+    /// Queue giving up ownership for good. Once executed, the contract account
+    /// becomes the owner, so nobody holds the Owner role and roles can no
+    /// longer be granted. Returns the queued action id.
+    #[payable]
+    pub fn renounce_ownership(&mut self) -> u64 {
+        assert_one_yocto();
+        require!(
+            env::predecessor_account_id() == self.owner_id,
+            "Only the current owner can renounce ownership."
+        );
+        require!(
+            !self.ownership_renounced,
+            "Ownership has already been renounced."
+        );
 
-        log!("Ownership successfully transferred to: {}", self.owner_id);
+        self.internal_queue_action(TimelockAction::RenounceOwnership)
     }
 
     /// Check Current and Proposed Owners
     pub fn get_owners(&self) -> (AccountId, Option<AccountId>) {
+        let proposed_owner = self
+            .pending_ownership_transfer
+            .as_ref()
+            .map(|pending| pending.proposed_owner.clone());
         log!("Owner ID: {}", self.owner_id.clone());
-        log!("Proposed Owner ID: {:?}", self.proposed_owner);
-        (self.owner_id.clone(), self.proposed_owner.clone())
+        log!("Proposed Owner ID: {:?}", proposed_owner);
+        (self.owner_id.clone(), proposed_owner)
+    }
+
+    /// Pending ownership transfer, including its proposal time and expiry
+    pub fn get_pending_ownership_transfer(&self) -> Option<PendingOwnershipTransfer> {
+        self.pending_ownership_transfer.clone()
+    }
+
+    /// Whether ownership has been renounced for good
+    pub fn is_ownership_renounced(&self) -> bool {
+        self.ownership_renounced
+    }
+
+    /// Returns the emission parameters currently in effect
+    pub fn get_emission_config(&self) -> EmissionConfig {
+        self.emission_config.clone()
+    }
+
+    /// Queue replacing the emission parameters behind the timelock. Once
+    /// executed, the decay factor applies from the next mint and the global
+    /// tapping pool resets to the new `tapping_reset` amount. `initial_emissions`
+    /// and `raffle_seed` are fixed at initialization and must be passed
+    /// unchanged. Returns the queued action id.
+    #[payable]
+    pub fn update_emission_config(&mut self, config: EmissionConfig) -> u64 {
+        assert_one_yocto();
+        self.assert_role(Role::ConfigAdmin);
+        config.assert_valid_update(&self.emission_config);
+
+        self.internal_queue_action(TimelockAction::UpdateEmissionConfig { config })
     }
 
+    /// Queue replacing the token metadata behind the timelock. Returns the
+    /// queued action id.
     #[payable]
-    pub fn mint(&mut self) {
+    pub fn update_metadata(&mut self, metadata: FungibleTokenMetadata) -> u64 {
+        assert_one_yocto();
+        self.assert_role(Role::ConfigAdmin);
+        metadata.assert_valid();
+
+        self.internal_queue_action(TimelockAction::UpdateMetadata { metadata })
+    }
+
+    /// Mint every emission epoch that has come due since the last mint, up to
+    /// `max_epochs` (default 12) per call. The schedule stays aligned to 30-day
+    /// epochs, so a late mint does not shift the dates of later ones.
+    #[payable]
+    pub fn mint(&mut self, max_epochs: Option<u32>) {
         assert_one_yocto();
         require!(
             env::attached_deposit() == NearToken::from_yoctonear(1),
@@ -228,104 +642,104 @@ impl Contract {
         log!("Caller ID: {}", caller_id);
         log!("Owner ID: {}", self.owner_id);
 
-        require!(caller_id == self.owner_id, "Caller is not the owner");
-
-        // Step 1: Retrieve emissions_account, loot_raffle_pool_account, and global_tapping_pool
-        let mut emissions_account = self
-            .emissions_account
-            .get(&self.owner_id.clone())
-            .expect("Emissions account not found");
-        let mut loot_raffle_pool_account = self
-            .loot_raffle_pool
-            .get(&1)
-            .expect("Loot raffle pool account not found");
-        let mut global_tapping_pool = self
-            .global_tapping_pool
-            .get(&2)
-            .expect("Global tapping pool not found");
-
-        // Step 2: Get the current Unix timestamp
-        let current_timestamp: u64 = env::block_timestamp() / 1_000_000_000; // Convert from nanoseconds to seconds
-
-        // Step 3: Define the required time interval in seconds (30 days = 2,592,000 seconds)
-        const SECONDS_IN_A_MONTH: u64 = 30 * 24 * 60 * 60;
-
-        // Step 4: Verify that the required interval has passed since the last mint if current_month > 0
-        if emissions_account.current_month > 0 {
-            let last_mint_timestamp = emissions_account.last_mint_timestamp.0 / 1_000_000_000; // Convert from nanoseconds to seconds
-            log!(
-                "Current timestamp: {}, Last mint timestamp: {}, Time passed: {}",
-                current_timestamp,
-                last_mint_timestamp,
-                current_timestamp - last_mint_timestamp
-            );
+        self.assert_role(Role::Minter);
 
-            require!(
-                current_timestamp - last_mint_timestamp >= SECONDS_IN_A_MONTH,
-                "The required interval has not yet passed"
-            );
-        }
+        self.multisig_assert_not_required_for_mint();
+        self.internal_mint_epochs(max_epochs);
+    }
 
-        // Step 5: Apply decay factor to current_emissions if current_month > 0
-        if emissions_account.current_month > 0 {
-            emissions_account.current_emissions = U64(
-                (emissions_account.current_emissions.0 as f64 * emissions_account.decay_factor)
-                    as u64,
-            );
-        }
+    /// Current emissions schedule state
+    pub fn get_emissions_account(&self) -> EmissionsAccount {
+        self.emissions.clone()
+    }
 
-        // Step 6: Calculate mint_amount by multiplying current_emissions by 100,000
-        let mint_amount = U128(
-            u128::from(emissions_account.current_emissions.0) // Explicitly convert u64 to u128
-                .checked_mul(100_000)
-                .expect("Mint amount multiplication overflow"),
-        );
+    /// Current state of all reward pools
+    pub fn get_pools(&self) -> Vec<RewardPool> {
+        self.pools.values().cloned().collect()
+    }
 
-        // Step 7: Execute the mint operation
-        self.token.internal_deposit(&self.owner_id, mint_amount.0);
+    /// Projected mints for `count` months starting at `from_month`,
+    /// computed with the same epoch logic `mint` uses.
+    pub fn get_emission_schedule(&self, from_month: u32, count: u32) -> Vec<EmissionForecast> {
+        require!(
+            count <= MAX_FORECAST_MONTHS,
+            format!("count must not exceed {}", MAX_FORECAST_MONTHS)
+        );
+        let mut emissions = self.emissions.clone();
+        require!(
+            from_month >= emissions.current_month,
+            format!(
+                "Month {} has already been minted; the schedule starts at month {}",
+                from_month, emissions.current_month
+            )
+        );
+        let horizon = emissions.current_month.saturating_add(MAX_FORECAST_MONTHS);
+        require!(
+            from_month <= horizon,
+            format!(
+                "The schedule can start at most {} months ahead, at month {}",
+                MAX_FORECAST_MONTHS, horizon
+            )
+        );
 
-        near_contract_standards::fungible_token::events::FtMint {
-            owner_id: &self.owner_id.clone(),
-            amount: mint_amount,
-            memo: Some("Tokens minted after emissions decay and interval reset"),
+        let decay_factor = self.emission_config.decay_factor;
+        let until_month = from_month.checked_add(count).expect("Month overflow");
+        let mut schedule = Vec::with_capacity(count as usize);
+        while emissions.current_month < until_month {
+            let month = emissions.current_month;
+            let timestamp = U64(emissions.next_mint_timestamp());
+            let amount = U128(emissions.advance_epoch(&decay_factor));
+            if month >= from_month {
+                schedule.push(EmissionForecast {
+                    month,
+                    timestamp,
+                    amount,
+                });
+            }
         }
-        .emit();
-
-        // Step 8: Reset global_tapping_pool.amount
-        global_tapping_pool.amount = U128(1_000_000_000_00000);
-        self.global_tapping_pool.insert(&2, &global_tapping_pool);
+        schedule
+    }
 
-        // Step 9: Apply decay factor to loot_raffle_pool_account.amount if current_month > 0
-        if emissions_account.current_month > 0 {
-            loot_raffle_pool_account.amount = U128(
-                (loot_raffle_pool_account.amount.0 as f64 * emissions_account.decay_factor) as u128,
-            );
-        }
+    /// Earliest time (nanoseconds) at which the next epoch can be minted
+    pub fn get_next_mint_time(&self) -> U64 {
+        U64(self.emissions.next_mint_timestamp())
+    }
 
-        // Step 10: Update loot_raffle_pool_account.total_amount
-        loot_raffle_pool_account.total_amount = U128(
-            loot_raffle_pool_account
-                .total_amount
-                .0
-                .checked_add(loot_raffle_pool_account.amount.0)
-                .expect("Total amount addition overflow"),
+    /// Total minted for months 0 through `until_month` inclusive, combining
+    /// what has already been minted with the projected schedule.
+    pub fn get_cumulative_emissions(&self, until_month: u32) -> U128 {
+        let mut emissions = self.emissions.clone();
+        require!(
+            until_month.checked_add(1).expect("Month overflow") >= emissions.current_month,
+            format!(
+                "Cumulative emissions are only available from month {} onward",
+                emissions.current_month.saturating_sub(1)
+            )
+        );
+        let horizon = emissions.current_month.saturating_add(MAX_FORECAST_MONTHS);
+        require!(
+            until_month <= horizon,
+            format!(
+                "Cumulative emissions can be projected at most {} months ahead, up to month {}",
+                MAX_FORECAST_MONTHS, horizon
+            )
         );
-        self.loot_raffle_pool.insert(&1, &loot_raffle_pool_account);
 
-        // Step 11: Update emissions_account
-        emissions_account.last_mint_timestamp = U64(env::block_timestamp()); // Keep this in nanoseconds
-        emissions_account.current_month = emissions_account
-            .current_month
-            .checked_add(1)
-            .expect("Current month addition overflow");
-        self.emissions_account
-            .insert(&self.owner_id, &emissions_account);
+        let decay_factor = self.emission_config.decay_factor;
+        while emissions.current_month <= until_month {
+            emissions.advance_epoch(&decay_factor);
+        }
+        emissions.total_minted
+    }
 
-        log!("Mint operation completed successfully!");
+    /// Hard cap on the total supply
+    pub fn ft_max_supply(&self) -> U128 {
+        self.max_supply
     }
 
     pub fn burn(&mut self, amount: U128) {
         // Step 1: Get the caller's account ID
+        self.assert_not_paused(PausableFeature::Burn);
         let caller_id = env::predecessor_account_id();
 
         // Step 2: Ensure the burn amount is greater than zero
@@ -343,12 +757,13 @@ impl Contract {
         );
 
         // Step 4: Withdraw the specified amount from the caller's account
+        self.internal_assert_escrow_unlocked(&caller_id, burn_amount);
         self.token.internal_withdraw(&caller_id, burn_amount);
 
         // Step 5: Emit a burn event
         near_contract_standards::fungible_token::events::FtBurn {
             owner_id: &caller_id,
-            amount: amount,
+            amount,
             memo: Some("Burning tokens from user's account"),
         }
         .emit();
@@ -366,127 +781,291 @@ impl Contract {
     ) {
         let caller_id: AccountId = env::predecessor_account_id();
         log!("Caller ID: {}", caller_id);
-        log!("Owner ID: {}", self.owner_id);
 
-        require!(
-            caller_id == self.owner_id,
-            "Caller is not the contract owner"
+        self.assert_role(Role::RewardDistributor);
+
+        self.multisig_assert_not_required_for_claim(amount.0);
+        self.internal_claim_rewards(amount, pool_id, user_account);
+    }
+}
+
+impl Contract {
+    /// Mint all due epochs. Callers check who may mint first.
+    pub(crate) fn internal_mint_epochs(&mut self, max_epochs: Option<u32>) {
+        self.assert_not_paused(PausableFeature::Mint);
+
+        // Step 1: Retrieve emissions_account
+        let mut emissions_account = self.emissions.clone();
+
+        // Step 2: Work out how many 30-day epochs are due, bounded by max_epochs for gas
+        let current_timestamp = env::block_timestamp();
+        let pending_epochs = emissions_account.pending_epochs(current_timestamp);
+        log!(
+            "Current timestamp: {}, Next mint timestamp: {}, Pending epochs: {}",
+            current_timestamp,
+            emissions_account.next_mint_timestamp(),
+            pending_epochs
+        );
+        require!(pending_epochs > 0, "The required interval has not yet passed");
+
+        let max_epochs = max_epochs.unwrap_or(DEFAULT_MAX_EPOCHS_PER_MINT);
+        require!(max_epochs > 0, "max_epochs must be greater than zero");
+        let epochs_to_mint = pending_epochs.min(u64::from(max_epochs));
+
+        // Step 3: Advance the schedule one epoch at a time, decaying emissions and
+        // refilling pools exactly as a sequence of on-time mints would have. Each
+        // epoch's emission funds its staking share first, then the pool refills;
+        // the owner receives the rest
+        let decay_factor = self.emission_config.decay_factor;
+        let mut mint_amount: u128 = 0;
+        let mut staking_amount: u128 = 0;
+        let (mut refilled_amount, mut decayed_amount) = (0u128, 0u128);
+        for _ in 0..epochs_to_mint {
+            let decay_applies = emissions_account.current_month > 0;
+            let epoch_amount = emissions_account.advance_epoch(&decay_factor);
+            let epoch_staking_amount = self.internal_staking_share(epoch_amount);
+            let (added, removed) =
+                self.internal_refill_pools(decay_applies, epoch_amount - epoch_staking_amount);
+            refilled_amount = refilled_amount
+                .checked_add(added)
+                .expect("Refill amount addition overflow");
+            decayed_amount = decayed_amount
+                .checked_add(removed)
+                .expect("Decay amount addition overflow");
+
+            mint_amount = mint_amount
+                .checked_add(epoch_amount)
+                .expect("Mint amount addition overflow");
+            staking_amount += epoch_staking_amount;
+        }
+
+        // Step 4: Mint the cumulative amount. The staking share and the net pool
+        // refill go into escrow, the rest to the owner, so the supply grows by
+        // exactly the scheduled emission
+        let escrow_amount = staking_amount + refilled_amount.saturating_sub(decayed_amount);
+        let owner_amount = mint_amount - escrow_amount;
+        let owner_id = self.owner_id.clone();
+        self.internal_mint(&owner_id, owner_amount);
+
+        near_contract_standards::fungible_token::events::FtMint {
+            owner_id: &self.owner_id.clone(),
+            amount: U128(owner_amount),
+            memo: Some("Tokens minted after emissions decay and interval reset"),
+        }
+        .emit();
+
+        // Step 5: Keep the escrowed balance in step with the refilled pools. What
+        // decaying pools give up beyond the refills is released to the owner
+        self.internal_mint_to_escrow(
+            refilled_amount.saturating_sub(decayed_amount),
+            "Reward pool refill",
+        );
+        let released_amount = decayed_amount.saturating_sub(refilled_amount);
+        if released_amount > 0 && owner_id != env::current_account_id() {
+            self.internal_pay_from_escrow(
+                &owner_id,
+                released_amount,
+                "Reward pool decay".to_string(),
+            );
+        }
+        self.internal_mint_to_escrow(staking_amount, "Staking rewards");
+        self.internal_fund_staking_rewards(staking_amount, epochs_to_mint);
+
+        // Step 6: Persist the epoch-aligned emissions schedule
+        self.emissions = emissions_account;
+
+        log!(
+            "Minted {} epochs, {} epochs still pending",
+            epochs_to_mint,
+            pending_epochs - epochs_to_mint
         );
+        log!("Mint operation completed successfully!");
+    }
+
+    /// Pay `amount` from a pool to `user_account`. Callers check who may
+    /// distribute rewards first.
+    pub(crate) fn internal_claim_rewards(
+        &mut self,
+        amount: U128,
+        pool_id: u32,
+        user_account: AccountId,
+    ) {
+        self.assert_not_paused(PausableFeature::ClaimRewards);
 
         // Step 1: Validate the amount to claim
         let amount_to_claim = amount.0; // Extract raw u128 from U128
         require!(amount_to_claim > 0, "Invalid amount to claim");
 
         // Step 2: Ensure the user account is registered
-        if self
-            .token
-            .storage_balance_of(user_account.clone())
-            .is_none()
-        {
-            let deposit_amount = self.token.storage_balance_bounds().min;
-            require!(
-            env::attached_deposit() >= deposit_amount,
-            "Attached deposit is less than the minimum storage balance required for account registration"
-        );
-            self.token.storage_deposit(Some(user_account.clone()), None);
-            log!("Storage deposit successful for account: {}", user_account);
-        }
-
-        // Step 3: Check and deduct the amount from the respective pool
-        match pool_id {
-            1 => {
-                // Loot Raffle Pool
-                let mut loot_pool = self
-                    .loot_raffle_pool
-                    .get(&1)
-                    .expect("Loot Raffle Pool not found");
-                require!(
-                    amount_to_claim <= loot_pool.amount.0,
-                    format!(
-                        "Insufficient funds in Loot Raffle Pool. Available: {}, Requested: {}",
-                        loot_pool.amount.0, amount_to_claim
-                    )
-                );
-                loot_pool.amount = U128(
-                    loot_pool
-                        .amount
-                        .0
-                        .checked_sub(amount_to_claim)
-                        .expect("Underflow in Loot Raffle Pool"),
-                );
-                self.loot_raffle_pool.insert(&1, &loot_pool);
-            }
-            2 => {
-                // Global Tapping Pool
-                let mut tapping_pool = self
-                    .global_tapping_pool
-                    .get(&2)
-                    .expect("Global Tapping Pool not found");
-                require!(
-                    amount_to_claim <= tapping_pool.amount.0,
-                    format!(
-                        "Insufficient funds in Global Tapping Pool. Available: {}, Requested: {}",
-                        tapping_pool.amount.0, amount_to_claim
-                    )
-                );
-                tapping_pool.amount = U128(
-                    tapping_pool
-                        .amount
-                        .0
-                        .checked_sub(amount_to_claim)
-                        .expect("Underflow in Global Tapping Pool"),
-                );
-                self.global_tapping_pool.insert(&2, &tapping_pool);
-            }
-            _ => {
-                // Invalid Pool ID
-                panic!("Invalid Pool ID");
-            }
-        }
+        self.internal_register_with_deposit(&user_account);
+
+        // Step 3: Apply any due scheduled refill and the pool's claim limits,
+        // then deduct the amount from the pool
+        self.internal_refill_before_claim(pool_id);
+        self.internal_consume_claim_allowance(pool_id, &user_account, amount_to_claim);
+        self.internal_withdraw_from_pool(pool_id, amount_to_claim);
 
-        // Step 4: Transfer the claimed amount to the user account
+        // Step 4: Transfer the claimed amount from escrow to the user account
         let transfer_amount = amount_to_claim
             .checked_mul(1) // Replace this multiplier with any scaling factor if required
             .expect("Overflow during transfer calculation");
 
-        self.token.ft_transfer(
-            user_account,
-            U128(transfer_amount),
-            Some(format!("Reward claim from pool_id: {}", pool_id)),
+        self.internal_pay_from_escrow(
+            &user_account,
+            transfer_amount,
+            format!("Reward claim from pool_id: {}", pool_id),
+        );
+        self.internal_record_claim(
+            &user_account,
+            pool_id,
+            transfer_amount,
+            ClaimSource::Distributor,
         );
 
         log!(
             "{} tokens claimed from Pool ID: {} by {}",
             transfer_amount,
             pool_id,
-            caller_id
+            env::predecessor_account_id()
         );
     }
-}
-
-/// Enforce the requirement of attaching exactly 1 yoctoⓃ for authentication
-fn assert_one_yocto() {
-    require!(
-        env::attached_deposit() == NearToken::from_yoctonear(1),
-        "Requires attached deposit of exactly 1 yoctoⓃ for authentication."
-    );
-}
 
-#[near]
-impl FungibleTokenCore for Contract {
-    #[payable]
-    fn ft_transfer(&mut self, receiver_id: AccountId, amount: U128, memo: Option<String>) {
-        self.token.ft_transfer(receiver_id, amount, memo)
+    /// Register `account_id` for storage out of the attached deposit if it is
+    /// not registered yet.
+    pub(crate) fn internal_register_with_deposit(&mut self, account_id: &AccountId) {
+        if self
+            .token
+            .storage_balance_of(account_id.clone())
+            .is_none()
+        {
+            let deposit_amount = self.token.storage_balance_bounds().min;
+            require!(
+            env::attached_deposit() >= deposit_amount,
+            "Attached deposit is less than the minimum storage balance required for account registration"
+        );
+            self.token.storage_deposit(Some(account_id.clone()), None);
+            log!("Storage deposit successful for account: {}", account_id);
+        }
     }
 
-    #[payable]
-    fn ft_transfer_call(
+    pub(crate) fn internal_propose_ownership_transfer(&mut self, new_owner: AccountId) {
+        require!(
+            !self.ownership_renounced,
+            "Ownership has already been renounced."
+        );
+        require!(
+            new_owner != self.owner_id,
+            "New owner cannot be the current owner."
+        );
+
+        let proposed_at = env::block_timestamp();
+        let expires_at = proposed_at
+            .checked_add(OWNERSHIP_TRANSFER_EXPIRY_NS)
+            .expect("Expiry timestamp overflow");
+        self.pending_ownership_transfer = Some(PendingOwnershipTransfer {
+            proposed_owner: new_owner.clone(),
+            proposed_at: U64(proposed_at),
+            expires_at: U64(expires_at),
+        });
+
+        ContractEvent::OwnershipTransferProposed {
+            owner_id: self.owner_id.clone(),
+            proposed_owner: new_owner,
+            expires_at: U64(expires_at),
+        }
+        .emit();
+    }
+
+    pub(crate) fn internal_renounce_ownership(&mut self) {
+        require!(
+            !self.ownership_renounced,
+            "Ownership has already been renounced."
+        );
+        let previous_owner = std::mem::replace(&mut self.owner_id, env::current_account_id());
+        self.ownership_renounced = true;
+        self.pending_ownership_transfer = None;
+        self.internal_revoke_all_roles(&previous_owner, &previous_owner);
+
+        ContractEvent::OwnershipRenounced { previous_owner }.emit();
+    }
+
+    pub(crate) fn internal_update_emission_config(&mut self, config: EmissionConfig) {
+        config.assert_valid_update(&self.emission_config);
+
+        if config.tapping_reset != self.emission_config.tapping_reset {
+            self.internal_set_pool_refill_policy(
+                GLOBAL_TAPPING_POOL_ID,
+                RefillPolicy::Reset {
+                    amount: config.tapping_reset,
+                },
+            );
+        }
+
+        let old_config = std::mem::replace(&mut self.emission_config, config.clone());
+        ContractEvent::EmissionConfigUpdated {
+            updated_by: env::predecessor_account_id(),
+            old_config,
+            new_config: config,
+        }
+        .emit();
+    }
+
+    pub(crate) fn internal_update_metadata(&mut self, metadata: FungibleTokenMetadata) {
+        metadata.assert_valid();
+        self.metadata.set(&metadata);
+
+        ContractEvent::MetadataUpdated {
+            updated_by: env::predecessor_account_id(),
+            metadata,
+        }
+        .emit();
+    }
+
+    /// Deposit newly created tokens, enforcing the `max_supply` cap. Every
+    /// path that increases the total supply must go through here.
+    fn internal_mint(&mut self, account_id: &AccountId, amount: u128) {
+        let total_supply = self.token.total_supply;
+        let new_supply = total_supply
+            .checked_add(amount)
+            .expect("Total supply overflow");
+        require!(
+            new_supply <= self.max_supply.0,
+            format!(
+                "Minting {} would exceed the max supply. Total supply: {}, Max supply: {}",
+                amount, total_supply, self.max_supply.0
+            )
+        );
+        self.token.internal_deposit(account_id, amount);
+    }
+}
+
+/// Enforce the requirement of attaching exactly 1 yoctoⓃ for authentication
+fn assert_one_yocto() {
+    require!(
+        env::attached_deposit() == NearToken::from_yoctonear(1),
+        "Requires attached deposit of exactly 1 yoctoⓃ for authentication."
+    );
+}
+
+#[near]
+impl FungibleTokenCore for Contract {
+    #[payable]
+    fn ft_transfer(&mut self, receiver_id: AccountId, amount: U128, memo: Option<String>) {
+        self.assert_not_paused(PausableFeature::FtTransfer);
+        self.internal_assert_escrow_unlocked(&env::predecessor_account_id(), amount.0);
+        self.token.ft_transfer(receiver_id, amount, memo)
+    }
+
+    #[payable]
+    fn ft_transfer_call(
         &mut self,
         receiver_id: AccountId,
         amount: U128,
         memo: Option<String>,
         msg: String,
     ) -> PromiseOrValue<U128> {
+        self.assert_not_paused(PausableFeature::FtTransferCall);
+        self.internal_assert_escrow_unlocked(&env::predecessor_account_id(), amount.0);
         self.token.ft_transfer_call(receiver_id, amount, memo, msg)
     }
 
@@ -536,6 +1115,11 @@ impl StorageManagement for Contract {
 
     #[payable]
     fn storage_unregister(&mut self, force: Option<bool>) -> bool {
+        // The contract account holds the pool escrow; closing it would burn it
+        require!(
+            env::predecessor_account_id() != env::current_account_id(),
+            "The escrow account cannot be unregistered"
+        );
         #[allow(unused_variables)]
         if let Some((account_id, balance)) = self.token.internal_storage_unregister(force) {
             log!("Closed @{} with {}", account_id, balance);
@@ -561,167 +1145,1647 @@ impl FungibleTokenMetadataProvider for Contract {
     }
 }
 
-// #[cfg(test)]
-// mod tests {
-//     use super::*;
-//     use near_sdk::test_utils::{accounts, VMContextBuilder};
-//     use near_sdk::testing_env;
-//     use near_sdk_contract_tools::owner;
-
-//     fn setup_context(is_view: bool) {
-//         let mut builder = VMContextBuilder::new();
-//         builder.current_account_id(accounts(0));
-//         builder.is_view(is_view);
-//         testing_env!(builder.build());
-//     }
-
-//     #[test]
-//     fn test_new_default_meta() {
-//         setup_context(false);
-
-//         let owner_id = accounts(0);
-//         let total_supply = U128(1_000_000_000_000); // 1 billion tokens with decimals
-//         let contract = Contract::new_default_meta(owner_id.clone(), total_supply);
-
-//         // Verify token metadata
-//         let metadata = contract.metadata.get().unwrap();
-//         assert_eq!(metadata.name, "PUSH THE BUTTON PTB");
-//         assert_eq!(metadata.symbol, "PUSH");
-//         assert_eq!(metadata.decimals, 5);
-
-//         // Verify emissions account
-//         let emissions_account = contract.emissions_account.get(&owner_id).unwrap();
-//         assert_eq!(emissions_account.initial_emissions, 3_000_000_000);
-//         assert_eq!(emissions_account.current_emissions, 3_000_000_000);
-
-//         // Verify loot raffle pool
-//         let raffle_pool = contract.loot_raffle_pool.get(&1).unwrap();
-
-//         assert_eq!(raffle_pool.pool_id, 1);
-//         assert_eq!(raffle_pool.amount, 50_000_000_00000);
-
-//         // Verify global tapping pool
-//         let tapping_pool = contract.global_tapping_pool.get(&2).unwrap();
-//         assert_eq!(tapping_pool.pool_id, 2);
-//         assert_eq!(tapping_pool.amount, 1_000_000_000_00000);
-
-//         // Verify token balance for owner
-//         assert_eq!(contract.token.ft_balance_of(owner_id), total_supply);
-
-//         println!("Test passed: `new_default_meta` initialized correctly");
-//     }
-
-//     #[test]
-//     fn test_mint_function() {
-//         setup_context(false);
-
-//         let owner_id = accounts(1);
-//         let total_supply = U128(1_000_000_000_000); // Initial supply
-//         let mut contract = Contract::new_default_meta(owner_id.clone(), total_supply);
-
-//         // Simulate a passage of time for minting
-//         let mut builder = VMContextBuilder::new();
-//         builder.current_account_id(accounts(0));
-//         builder.block_timestamp(60 * 1_000_000_000); // 1 minute has passed in nanoseconds
-//         testing_env!(builder.build());
-
-//         // Perform minting
-//         contract.mint(owner_id.clone());
-
-//         // Check updated emissions account
-//         let emissions_account = contract.emissions_account.get(&owner_id).unwrap();
-//         assert_eq!(emissions_account.current_month, 1); // Current month incremented
-//         assert_eq!(
-//             emissions_account.current_emissions,
-//             3_000_000_000u64
-//         ); // Emissions reduced by decay factor
-//         assert_eq!(emissions_account.last_mint_timestamp, 60 * 1_000_000_000); // Timestamp updated
-
-//         // Check global tapping pool
-//         let tapping_pool = contract.global_tapping_pool.get(&2).unwrap();
-//         assert_eq!(tapping_pool.amount, 1_000_000_000_00000); // Reset to default
-
-//         // Check loot raffle pool
-//         let raffle_pool = contract.loot_raffle_pool.get(&1).unwrap();
-//         assert_eq!(
-//             raffle_pool.amount,
-//             50_000_000_00000u128
-//         ); // Decayed amount
-
-//         // Check owner's new balance
-//         let expected_mint_amount = U128(3_000_000_000 as u128 * 100_000); // Adjusted for decimals
-//         let new_balance = contract.token.ft_balance_of(owner_id.clone());
-//         assert_eq!(
-//             new_balance.0,
-//             total_supply.0 + expected_mint_amount.0
-//         ); // Balance includes minted amount
-
-//         println!("Test passed: `mint` function executed correctly!");
-//     }
-
-//     #[test]
-//     fn test_claim_rewards() {
-//         setup_context(false);
-
-//         let owner_id = accounts(0); // Contract owner
-//         let user_id: AccountId = "user1234test.testnet".parse().unwrap(); // Hardcoded user ID
-//         let total_supply = U128(1_000_000_000_000); // Initial total supply
-//         let mut contract = Contract::new_default_meta(owner_id.clone(), total_supply);
-
-//         // Setup initial pools
-//         let initial_loot_pool_amount = 50_000_000_00000;
-//         let initial_tapping_pool_amount = 1_000_000_000_00000;
-
-//         // Verify initial loot raffle pool amount
-//         let loot_pool = contract.loot_raffle_pool.get(&1).unwrap();
-//         assert_eq!(loot_pool.amount, initial_loot_pool_amount);
-
-//         // Verify initial global tapping pool amount
-//         let tapping_pool = contract.global_tapping_pool.get(&2).unwrap();
-//         assert_eq!(tapping_pool.amount, initial_tapping_pool_amount);
-
-//         // Register user account for rewards (simulate storage deposit)
-//         contract.token.internal_register_account(&user_id);
-
-//         // Ensure the owner has sufficient balance
-//         let owner_balance = contract.token.ft_balance_of(owner_id.clone()).0;
-//         println!("line 502 balance {} {}", owner_balance, owner_id);
-//         assert!(
-//             owner_balance >= 1,
-//             "Owner does not have sufficient balance to claim rewards"
-//         );
-
-//         // Ensure the pool has sufficient balance for the claim
-//         let claim_amount = 1; // Claim 1 (scaled in the method)
-//         let scaled_claim_amount = claim_amount as u128 * 100_000;
-//         assert!(
-//             loot_pool.amount >= scaled_claim_amount,
-//             "Loot pool does not have sufficient balance to satisfy the claim"
-//         );
-
-//         // Simulate the required attached deposit of 1 yoctoNEAR
-//         let mut builder = VMContextBuilder::new();
-//         builder
-//             .attached_deposit(NearToken::from_yoctonear(1)) // Attach 1 yoctoNEAR
-//             .predecessor_account_id(owner_id.clone()) // Owner is sending rewards
-//             .current_account_id(owner_id.clone()); // Contract owner
-//         testing_env!(builder.build());
-
-//         // Test claiming rewards from loot raffle pool
-//         contract.claim_rewards(claim_amount * 100000, 1, user_id.clone());
-
-//         // Verify updated loot raffle pool
-//         let updated_loot_pool = contract.loot_raffle_pool.get(&1).unwrap();
-//         assert_eq!(
-//             updated_loot_pool.amount,
-//             initial_loot_pool_amount - scaled_claim_amount
-//         );
-
-//         // Verify user balance after claim
-//         let updated_user_balance = contract.token.ft_balance_of(user_id.clone()).0;
-//         assert_eq!(updated_user_balance, scaled_claim_amount);
-
-//         println!("Test passed: `claim_rewards` executed correctly!");
-//     }
-
-// }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accrual::EPOCH_CLAIM_WINDOW;
+    use crate::claims::ClaimStatus;
+    use crate::history::ClaimSource;
+    use crate::limits::ClaimLimits;
+    use crate::merkle::claim_leaf_hash;
+    use crate::multisig::MultisigAction;
+    use crate::raffle::RaffleStatus;
+    use crate::tapping::{RefillPeriod, RemainderPolicy, TappingRefillConfig};
+    use crate::taps::TapReport;
+    use crate::vouchers::RewardVoucher;
+    use ed25519_dalek::{Signer, SigningKey};
+    use near_contract_standards::fungible_token::receiver::FungibleTokenReceiver;
+    use near_sdk::json_types::{Base58CryptoHash, Base64VecU8};
+    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::{testing_env, CryptoHash};
+
+    const TOTAL_SUPPLY: u128 = 2_000_000_000_000_000; // 20 billion tokens with decimals
+
+    fn setup_context(predecessor: AccountId, deposit: NearToken, timestamp: u64) {
+        let mut builder = VMContextBuilder::new();
+        builder
+            .current_account_id(accounts(5))
+            .predecessor_account_id(predecessor)
+            .attached_deposit(deposit)
+            .block_timestamp(timestamp);
+        testing_env!(builder.build());
+    }
+
+    /// Contract owned by `accounts(0)`, initialized at timestamp 0 with the default config.
+    fn setup_contract() -> Contract {
+        setup_context(accounts(0), NearToken::from_yoctonear(0), 0);
+        Contract::new_default_meta(U128(TOTAL_SUPPLY), U128(u128::MAX), None)
+    }
+
+    fn storage_deposit() -> NearToken {
+        NearToken::from_yoctonear(10u128.pow(22)) // 0.01 NEAR, above the registration minimum
+    }
+
+    #[test]
+    fn test_new_default_meta() {
+        let contract = setup_contract();
+        let config = EmissionConfig::default();
+
+        // Verify token metadata
+        let metadata = contract.ft_metadata();
+        assert_eq!(metadata.name, "PUSH THE BUTTON PTB");
+        assert_eq!(metadata.symbol, "PUSH");
+        assert_eq!(metadata.decimals, 5);
+
+        // Verify emissions account
+        let emissions_account = contract.get_emissions_account();
+        assert_eq!(emissions_account.initial_emissions.0, 3_000_000_000);
+        assert_eq!(emissions_account.current_emissions.0, 3_000_000_000);
+        assert_eq!(emissions_account.current_month, 0);
+
+        // Verify loot raffle pool and global tapping pool
+        let raffle_pool = contract.get_raffle_pool();
+        assert_eq!(raffle_pool.pool_id, 1);
+        assert_eq!(raffle_pool.amount, config.raffle_seed);
+        let tapping_pool = contract.get_tapping_pool();
+        assert_eq!(tapping_pool.pool_id, 2);
+        assert_eq!(tapping_pool.amount, config.tapping_reset);
+
+        // The initial pool allocations move out of the owner's supply into escrow
+        let pool_amount = config.raffle_seed.0 + config.tapping_reset.0;
+        assert_eq!(
+            contract.ft_balance_of(accounts(0)).0,
+            TOTAL_SUPPLY - pool_amount
+        );
+        assert_eq!(contract.ft_balance_of(accounts(5)).0, pool_amount);
+        assert_eq!(contract.ft_total_supply().0, TOTAL_SUPPLY);
+    }
+
+    #[test]
+    fn test_mint_function() {
+        let mut contract = setup_contract();
+        let owner_balance = contract.ft_balance_of(accounts(0)).0;
+
+        // Month 0 is due as soon as the contract is initialized
+        setup_context(accounts(0), NearToken::from_yoctonear(1), 0);
+        contract.mint(None);
+
+        let emissions_account = contract.get_emissions_account();
+        assert_eq!(emissions_account.current_month, 1);
+        assert_eq!(emissions_account.current_emissions.0, 3_000_000_000);
+        assert_eq!(emissions_account.last_mint_timestamp.0, 0);
+
+        // Month 0 neither decays the raffle pool nor changes the tapping pool,
+        // so the whole emission goes to the owner
+        let config = EmissionConfig::default();
+        assert_eq!(contract.get_tapping_pool().amount, config.tapping_reset);
+        assert_eq!(contract.get_raffle_pool().amount, config.raffle_seed);
+        assert_eq!(
+            contract.ft_balance_of(accounts(0)).0,
+            owner_balance + 3_000_000_000 * TOKEN_UNIT
+        );
+    }
+
+    #[test]
+    fn test_claim_rewards() {
+        let mut contract = setup_contract();
+        let user_id: AccountId = "user1234test.testnet".parse().unwrap();
+        let initial_loot_pool_amount = contract.get_raffle_pool().amount.0;
+        let claim_amount = 100_000; // 1 token
+
+        // The storage deposit attached by the distributor registers the user
+        setup_context(accounts(0), storage_deposit(), 0);
+        contract.claim_rewards(U128(claim_amount), 1, user_id.clone());
+
+        assert_eq!(
+            contract.get_raffle_pool().amount.0,
+            initial_loot_pool_amount - claim_amount
+        );
+        assert_eq!(contract.ft_balance_of(user_id).0, claim_amount);
+        assert!(contract.get_escrow_status().is_solvent);
+    }
+
+    #[test]
+    fn test_decay_factor_rounds_down() {
+        let decay_factor = DecayFactor {
+            numerator: U128(8_705_505_633),
+            denominator: U128(10_000_000_000),
+        };
+        assert_eq!(decay_factor.apply(3_000_000_000), 2_611_651_689);
+        assert_eq!(decay_factor.apply(2_611_651_689), 2_273_574_849);
+        assert_eq!(decay_factor.apply(0), 0);
+
+        let identity = DecayFactor {
+            numerator: U128(1),
+            denominator: U128(1),
+        };
+        assert_eq!(identity.apply(3_000_000_000), 3_000_000_000);
+    }
+
+    #[test]
+    fn test_mint_catches_up_missed_epochs() {
+        let mut contract = setup_contract();
+        let supply = contract.ft_total_supply().0;
+        let expected = contract.get_cumulative_emissions(2).0;
+
+        // Three epochs (months 0, 1 and 2) are due two epochs after initialization
+        setup_context(
+            accounts(0),
+            NearToken::from_yoctonear(1),
+            2 * EMISSION_EPOCH_NS + 5,
+        );
+        contract.mint(None);
+
+        let decay_factor = contract.get_emission_config().decay_factor;
+        let month_1 = decay_factor.apply(3_000_000_000);
+        let month_2 = decay_factor.apply(month_1);
+        assert_eq!(expected, (3_000_000_000 + month_1 + month_2) * TOKEN_UNIT);
+
+        let emissions_account = contract.get_emissions_account();
+        assert_eq!(emissions_account.current_month, 3);
+        assert_eq!(u128::from(emissions_account.current_emissions.0), month_2);
+        assert_eq!(emissions_account.total_minted.0, expected);
+        // The schedule advances in whole epochs, not to the block time
+        assert_eq!(
+            emissions_account.last_mint_timestamp.0,
+            2 * EMISSION_EPOCH_NS
+        );
+        assert_eq!(contract.ft_total_supply().0, supply + expected);
+        assert!(contract.get_escrow_status().is_solvent);
+    }
+
+    #[test]
+    fn test_mint_catch_up_is_bounded_by_max_epochs() {
+        let mut contract = setup_contract();
+
+        // Five epochs are due, but only two are minted per call
+        setup_context(
+            accounts(0),
+            NearToken::from_yoctonear(1),
+            4 * EMISSION_EPOCH_NS,
+        );
+        contract.mint(Some(2));
+        assert_eq!(contract.get_emissions_account().current_month, 2);
+        contract.mint(None);
+        assert_eq!(contract.get_emissions_account().current_month, 5);
+        assert_eq!(
+            contract.get_emissions_account().total_minted.0,
+            contract.get_cumulative_emissions(4).0
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "The required interval has not yet passed")]
+    fn test_mint_rejects_early_call() {
+        let mut contract = setup_contract();
+        setup_context(accounts(0), NearToken::from_yoctonear(1), 0);
+        contract.mint(None);
+        setup_context(
+            accounts(0),
+            NearToken::from_yoctonear(1),
+            EMISSION_EPOCH_NS - 1,
+        );
+        contract.mint(None);
+    }
+
+    /// Execute a queued action as the owner at its ETA.
+    fn execute_timelocked(contract: &mut Contract, action_id: u64) {
+        let queued = contract
+            .get_queued_actions(None, None)
+            .into_iter()
+            .find(|queued| queued.action_id == action_id)
+            .expect("Action is not queued");
+        setup_context(accounts(0), NearToken::from_yoctonear(1), queued.eta.0);
+        contract.execute_timelock_action(action_id);
+    }
+
+    #[test]
+    fn test_update_emission_config_applies_tapping_reset() {
+        let mut contract = setup_contract();
+        let mut config = contract.get_emission_config();
+        config.tapping_reset = U128(5 * TOKEN_UNIT);
+        config.decay_factor = DecayFactor {
+            numerator: U128(9),
+            denominator: U128(10),
+        };
+
+        setup_context(accounts(0), NearToken::from_yoctonear(1), 0);
+        let action_id = contract.update_emission_config(config.clone());
+        execute_timelocked(&mut contract, action_id);
+
+        assert_eq!(contract.get_emission_config(), config);
+        assert_eq!(
+            contract.get_tapping_pool().refill_policy,
+            RefillPolicy::Reset {
+                amount: U128(5 * TOKEN_UNIT)
+            }
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Raffle seed cannot be changed after initialization")]
+    fn test_update_emission_config_rejects_raffle_seed_change() {
+        let mut contract = setup_contract();
+        let mut config = contract.get_emission_config();
+        config.raffle_seed = U128(1);
+
+        setup_context(accounts(0), NearToken::from_yoctonear(1), 0);
+        contract.update_emission_config(config);
+    }
+
+    #[test]
+    #[should_panic(expected = "The escrow account cannot be unregistered")]
+    fn test_storage_unregister_rejects_escrow_account() {
+        let mut contract = setup_contract();
+        setup_context(accounts(5), NearToken::from_yoctonear(1), 0);
+        contract.storage_unregister(Some(true));
+    }
+
+    #[test]
+    #[should_panic(expected = "does not have the RewardDistributor role")]
+    fn test_claim_rewards_requires_distributor_role() {
+        let mut contract = setup_contract();
+        setup_context(accounts(1), storage_deposit(), 0);
+        contract.claim_rewards(U128(TOKEN_UNIT), 1, accounts(2));
+    }
+
+    #[test]
+    #[should_panic(expected = "does not have the Minter role")]
+    fn test_mint_requires_minter_role() {
+        let mut contract = setup_contract();
+        setup_context(accounts(1), NearToken::from_yoctonear(1), EMISSION_EPOCH_NS);
+        contract.mint(None);
+    }
+
+    #[test]
+    fn test_grant_and_revoke_role() {
+        let mut contract = setup_contract();
+        assert!(Role::ALL
+            .into_iter()
+            .all(|role| contract.has_role(accounts(0), role)));
+
+        setup_context(accounts(0), NearToken::from_yoctonear(1), 0);
+        contract.grant_role(accounts(1), Role::RewardDistributor);
+        assert_eq!(
+            contract.get_role_members(Role::RewardDistributor),
+            vec![accounts(0), accounts(1)]
+        );
+
+        // The new distributor can pay out rewards
+        setup_context(accounts(1), storage_deposit(), 0);
+        contract.claim_rewards(U128(TOKEN_UNIT), 1, accounts(2));
+        assert_eq!(contract.ft_balance_of(accounts(2)).0, TOKEN_UNIT);
+
+        setup_context(accounts(0), NearToken::from_yoctonear(1), 0);
+        contract.revoke_role(accounts(1), Role::RewardDistributor);
+        assert!(!contract.has_role(accounts(1), Role::RewardDistributor));
+    }
+
+    #[test]
+    #[should_panic(expected = "cannot be granted directly")]
+    fn test_owner_role_cannot_be_granted() {
+        let mut contract = setup_contract();
+        setup_context(accounts(0), NearToken::from_yoctonear(1), 0);
+        contract.grant_role(accounts(1), Role::Owner);
+    }
+
+    #[test]
+    #[should_panic(expected = "does not have the Owner role")]
+    fn test_grant_role_requires_owner() {
+        let mut contract = setup_contract();
+        setup_context(accounts(1), NearToken::from_yoctonear(1), 0);
+        contract.grant_role(accounts(1), Role::Minter);
+    }
+
+    fn pause_feature(contract: &mut Contract, feature: PausableFeature) {
+        setup_context(accounts(0), NearToken::from_yoctonear(1), 0);
+        contract.pause(feature);
+    }
+
+    #[test]
+    #[should_panic(expected = "FtTransfer is paused")]
+    fn test_paused_ft_transfer() {
+        let mut contract = setup_contract();
+        pause_feature(&mut contract, PausableFeature::FtTransfer);
+        setup_context(accounts(0), NearToken::from_yoctonear(1), 0);
+        contract.ft_transfer(accounts(1), U128(1), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "ClaimRewards is paused")]
+    fn test_paused_claim_rewards() {
+        let mut contract = setup_contract();
+        pause_feature(&mut contract, PausableFeature::ClaimRewards);
+        setup_context(accounts(0), storage_deposit(), 0);
+        contract.claim_rewards(U128(TOKEN_UNIT), 1, accounts(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "Mint is paused")]
+    fn test_paused_mint() {
+        let mut contract = setup_contract();
+        pause_feature(&mut contract, PausableFeature::Mint);
+        setup_context(accounts(0), NearToken::from_yoctonear(1), EMISSION_EPOCH_NS);
+        contract.mint(None);
+    }
+
+    #[test]
+    fn test_pause_flags_are_independent() {
+        let mut contract = setup_contract();
+        pause_feature(&mut contract, PausableFeature::Burn);
+        assert!(contract.is_paused(PausableFeature::Burn));
+        assert_eq!(contract.get_paused_features(), vec![PausableFeature::Burn]);
+
+        // Other features keep working while burn is paused
+        setup_context(accounts(0), storage_deposit(), 0);
+        contract.claim_rewards(U128(TOKEN_UNIT), 1, accounts(1));
+
+        setup_context(accounts(0), NearToken::from_yoctonear(1), 0);
+        contract.unpause(PausableFeature::Burn);
+        assert!(contract.get_paused_features().is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "does not have the Pauser role")]
+    fn test_pause_requires_pauser_role() {
+        let mut contract = setup_contract();
+        setup_context(accounts(1), NearToken::from_yoctonear(1), 0);
+        contract.pause(PausableFeature::Mint);
+    }
+
+    /// Queue and execute a transfer to `accounts(1)`, returning the time the
+    /// proposal was made.
+    fn propose_ownership_transfer(contract: &mut Contract) -> u64 {
+        setup_context(accounts(0), NearToken::from_yoctonear(1), 0);
+        let action_id = contract.initiate_ownership_transfer(accounts(1));
+        execute_timelocked(contract, action_id);
+        let pending = contract.get_pending_ownership_transfer().unwrap();
+        pending.proposed_at.0
+    }
+
+    #[test]
+    fn test_accept_ownership_moves_owner_role() {
+        let mut contract = setup_contract();
+        let proposed_at = propose_ownership_transfer(&mut contract);
+        assert_eq!(contract.get_owners(), (accounts(0), Some(accounts(1))));
+
+        setup_context(accounts(1), NearToken::from_yoctonear(1), proposed_at);
+        contract.accept_ownership();
+
+        assert_eq!(contract.get_owners(), (accounts(1), None));
+        assert!(contract.has_role(accounts(1), Role::Owner));
+        assert!(!contract.has_role(accounts(0), Role::Owner));
+    }
+
+    #[test]
+    #[should_panic(expected = "The ownership transfer proposal has expired.")]
+    fn test_accept_ownership_after_expiry() {
+        let mut contract = setup_contract();
+        let proposed_at = propose_ownership_transfer(&mut contract);
+
+        setup_context(
+            accounts(1),
+            NearToken::from_yoctonear(1),
+            proposed_at + OWNERSHIP_TRANSFER_EXPIRY_NS,
+        );
+        contract.accept_ownership();
+    }
+
+    #[test]
+    fn test_cancel_ownership_transfer() {
+        let mut contract = setup_contract();
+        propose_ownership_transfer(&mut contract);
+
+        // A second transfer still waiting in the timelock is dropped as well
+        setup_context(accounts(0), NearToken::from_yoctonear(1), 0);
+        contract.initiate_ownership_transfer(accounts(2));
+        contract.cancel_ownership_transfer();
+
+        assert!(contract.get_pending_ownership_transfer().is_none());
+        assert!(contract.get_queued_actions(None, None).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "No ownership transfer initiated.")]
+    fn test_accept_ownership_after_cancel() {
+        let mut contract = setup_contract();
+        let proposed_at = propose_ownership_transfer(&mut contract);
+        setup_context(accounts(0), NearToken::from_yoctonear(1), proposed_at);
+        contract.cancel_ownership_transfer();
+
+        setup_context(accounts(1), NearToken::from_yoctonear(1), proposed_at);
+        contract.accept_ownership();
+    }
+
+    #[test]
+    #[should_panic(expected = "is timelocked until")]
+    fn test_timelock_action_before_eta() {
+        let mut contract = setup_contract();
+        setup_context(accounts(0), NearToken::from_yoctonear(1), 0);
+        let action_id = contract.set_timelock_delay(U64(3 * DEFAULT_TIMELOCK_DELAY_NS));
+
+        setup_context(
+            accounts(0),
+            NearToken::from_yoctonear(1),
+            DEFAULT_TIMELOCK_DELAY_NS - 1,
+        );
+        contract.execute_timelock_action(action_id);
+    }
+
+    #[test]
+    fn test_timelock_action_at_eta() {
+        let mut contract = setup_contract();
+        setup_context(accounts(0), NearToken::from_yoctonear(1), 0);
+        let action_id = contract.set_timelock_delay(U64(3 * DEFAULT_TIMELOCK_DELAY_NS));
+        let queued = contract.get_queued_actions(None, None);
+        assert_eq!(queued[0].eta.0, DEFAULT_TIMELOCK_DELAY_NS);
+
+        execute_timelocked(&mut contract, action_id);
+        assert_eq!(
+            contract.get_timelock_delay().0,
+            3 * DEFAULT_TIMELOCK_DELAY_NS
+        );
+        assert!(contract.get_queued_actions(None, None).is_empty());
+    }
+
+    #[test]
+    fn test_expired_timelock_action_is_dropped() {
+        let mut contract = setup_contract();
+        setup_context(accounts(0), NearToken::from_yoctonear(1), 0);
+        let action_id = contract.set_timelock_delay(U64(3 * DEFAULT_TIMELOCK_DELAY_NS));
+        let expires_at = contract.get_queued_actions(None, None)[0].expires_at.0;
+
+        setup_context(accounts(0), NearToken::from_yoctonear(1), expires_at + 1);
+        contract.execute_timelock_action(action_id);
+
+        assert_eq!(contract.get_timelock_delay().0, DEFAULT_TIMELOCK_DELAY_NS);
+        assert!(contract.get_queued_actions(None, None).is_empty());
+        assert!(near_sdk::test_utils::get_logs()
+            .iter()
+            .any(|log| log.contains("timelock_action_expired")));
+    }
+
+    #[test]
+    #[should_panic(expected = "Timelock delay must be between")]
+    fn test_timelock_delay_minimum() {
+        let mut contract = setup_contract();
+        setup_context(accounts(0), NearToken::from_yoctonear(1), 0);
+        contract.set_timelock_delay(U64(0));
+    }
+
+    #[test]
+    #[should_panic(expected = "Timelock delay must be between")]
+    fn test_timelock_delay_maximum() {
+        let mut contract = setup_contract();
+        setup_context(accounts(0), NearToken::from_yoctonear(1), 0);
+        contract.set_timelock_delay(U64(16 * DEFAULT_TIMELOCK_DELAY_NS));
+    }
+
+    const LARGE_CLAIM_AMOUNT: u128 = 1_000 * TOKEN_UNIT;
+
+    /// Require 2 approvals from `signers` for mints and for claims above
+    /// `LARGE_CLAIM_AMOUNT`.
+    fn setup_multisig(contract: &mut Contract, signers: Vec<AccountId>) {
+        setup_context(accounts(0), NearToken::from_yoctonear(1), 0);
+        let action_id = contract.set_multisig_config(MultisigConfig {
+            signers,
+            mint_threshold: 2,
+            large_claim_threshold: 2,
+            large_claim_amount: U128(LARGE_CLAIM_AMOUNT),
+        });
+        execute_timelocked(contract, action_id);
+    }
+
+    fn propose_large_claim(contract: &mut Contract) -> u64 {
+        setup_context(accounts(0), NearToken::from_yoctonear(1), 0);
+        contract.propose_action(MultisigAction::ClaimRewards {
+            pool_id: LOOT_RAFFLE_POOL_ID,
+            amount: U128(LARGE_CLAIM_AMOUNT + 1),
+            user_account: accounts(4),
+        })
+    }
+
+    #[test]
+    #[should_panic(expected = "require multisig approval; use propose_action")]
+    fn test_large_claim_requires_multisig() {
+        let mut contract = setup_contract();
+        setup_multisig(&mut contract, vec![accounts(0), accounts(1), accounts(2)]);
+
+        // Claims up to the limit keep the single-role path
+        setup_context(accounts(0), storage_deposit(), 0);
+        contract.claim_rewards(U128(LARGE_CLAIM_AMOUNT), LOOT_RAFFLE_POOL_ID, accounts(4));
+        contract.claim_rewards(
+            U128(LARGE_CLAIM_AMOUNT + 1),
+            LOOT_RAFFLE_POOL_ID,
+            accounts(4),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Mint requires multisig approval; use propose_action")]
+    fn test_mint_requires_multisig() {
+        let mut contract = setup_contract();
+        setup_multisig(&mut contract, vec![accounts(0), accounts(1), accounts(2)]);
+        setup_context(accounts(0), NearToken::from_yoctonear(1), EMISSION_EPOCH_NS);
+        contract.mint(None);
+    }
+
+    #[test]
+    fn test_multisig_claim_meets_threshold() {
+        let mut contract = setup_contract();
+        setup_multisig(&mut contract, vec![accounts(0), accounts(1), accounts(2)]);
+        let proposal_id = propose_large_claim(&mut contract);
+
+        setup_context(accounts(1), NearToken::from_yoctonear(1), 0);
+        contract.approve_action(proposal_id);
+        setup_context(accounts(0), storage_deposit(), 0);
+        contract.execute_action(proposal_id);
+
+        assert_eq!(
+            contract.ft_balance_of(accounts(4)).0,
+            LARGE_CLAIM_AMOUNT + 1
+        );
+        assert!(contract.get_multisig_proposal(proposal_id).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "has 1 of 2 required approvals")]
+    fn test_multisig_claim_below_threshold() {
+        let mut contract = setup_contract();
+        setup_multisig(&mut contract, vec![accounts(0), accounts(1), accounts(2)]);
+        let proposal_id = propose_large_claim(&mut contract);
+
+        setup_context(accounts(0), storage_deposit(), 0);
+        contract.execute_action(proposal_id);
+    }
+
+    #[test]
+    #[should_panic(expected = "has 1 of 2 required approvals")]
+    fn test_multisig_ignores_removed_signer_approvals() {
+        let mut contract = setup_contract();
+        setup_multisig(&mut contract, vec![accounts(0), accounts(1), accounts(2)]);
+        let proposal_id = propose_large_claim(&mut contract);
+        setup_context(accounts(1), NearToken::from_yoctonear(1), 0);
+        contract.approve_action(proposal_id);
+
+        // accounts(1) is removed after approving
+        setup_multisig(&mut contract, vec![accounts(0), accounts(2)]);
+        assert_eq!(
+            contract
+                .get_multisig_proposal(proposal_id)
+                .unwrap()
+                .approvals,
+            vec![accounts(0), accounts(1)]
+        );
+
+        setup_context(accounts(0), storage_deposit(), 0);
+        contract.execute_action(proposal_id);
+    }
+
+    fn register(contract: &mut Contract, account_id: AccountId) {
+        setup_context(account_id.clone(), storage_deposit(), 0);
+        contract.storage_deposit(Some(account_id), None);
+    }
+
+    fn set_claim_limits(contract: &mut Contract, pool_id: u32, limits: ClaimLimits) {
+        setup_context(accounts(0), NearToken::from_yoctonear(1), 0);
+        let action_id = contract.set_pool_claim_limits(pool_id, limits);
+        execute_timelocked(contract, action_id);
+    }
+
+    #[test]
+    fn test_claim_rewards_batch_statuses() {
+        let mut contract = setup_contract();
+        setup_multisig(&mut contract, vec![accounts(0), accounts(1)]);
+        register(&mut contract, accounts(1));
+        register(&mut contract, accounts(2));
+        let initial_pool_amount = contract.get_raffle_pool().amount.0;
+
+        setup_context(accounts(0), NearToken::from_yoctonear(1), 0);
+        let results = contract.claim_rewards_batch(
+            LOOT_RAFFLE_POOL_ID,
+            vec![
+                (accounts(1), U128(TOKEN_UNIT)),
+                (accounts(3), U128(TOKEN_UNIT)),
+                (accounts(2), U128(0)),
+                (accounts(5), U128(TOKEN_UNIT)),
+                (accounts(4), U128(LARGE_CLAIM_AMOUNT + 1)),
+                (accounts(2), U128(TOKEN_UNIT + 1)),
+                (accounts(1), U128(TOKEN_UNIT)),
+            ],
+        );
+
+        let statuses: Vec<ClaimStatus> = results.iter().map(|result| result.status).collect();
+        assert_eq!(
+            statuses,
+            vec![
+                ClaimStatus::Paid,
+                ClaimStatus::NotRegistered,
+                ClaimStatus::InvalidAmount,
+                ClaimStatus::InvalidRecipient,
+                ClaimStatus::RequiresMultisig,
+                ClaimStatus::DuplicateRecipient,
+                ClaimStatus::DuplicateRecipient,
+            ]
+        );
+        assert_eq!(contract.ft_balance_of(accounts(1)).0, TOKEN_UNIT);
+        assert_eq!(contract.ft_balance_of(accounts(2)).0, 0);
+        assert_eq!(
+            contract.get_raffle_pool().amount.0,
+            initial_pool_amount - TOKEN_UNIT
+        );
+    }
+
+    #[test]
+    fn test_claim_rewards_batch_limit_exceeded() {
+        let mut contract = setup_contract();
+        set_claim_limits(
+            &mut contract,
+            LOOT_RAFFLE_POOL_ID,
+            ClaimLimits {
+                max_per_claim: Some(U128(TOKEN_UNIT)),
+                ..Default::default()
+            },
+        );
+        register(&mut contract, accounts(1));
+        register(&mut contract, accounts(2));
+
+        setup_context(accounts(0), NearToken::from_yoctonear(1), 0);
+        let results = contract.claim_rewards_batch(
+            LOOT_RAFFLE_POOL_ID,
+            vec![
+                (accounts(1), U128(TOKEN_UNIT + 1)),
+                (accounts(2), U128(TOKEN_UNIT)),
+            ],
+        );
+
+        assert_eq!(results[0].status, ClaimStatus::LimitExceeded);
+        assert_eq!(results[1].status, ClaimStatus::Paid);
+        assert_eq!(contract.ft_balance_of(accounts(1)).0, 0);
+    }
+
+    /// Root of a two-leaf tree, hashed the way `claim_with_proof` expects.
+    fn merkle_root(leaf_a: CryptoHash, leaf_b: CryptoHash) -> CryptoHash {
+        let (left, right) = if leaf_a <= leaf_b {
+            (leaf_a, leaf_b)
+        } else {
+            (leaf_b, leaf_a)
+        };
+        let mut data = vec![0x01];
+        data.extend_from_slice(&left);
+        data.extend_from_slice(&right);
+        env::sha256_array(&data)
+    }
+
+    #[test]
+    fn test_claim_leaf_hash() {
+        setup_context(accounts(0), NearToken::from_yoctonear(0), 0);
+        let mut data = vec![0x00];
+        data.extend_from_slice(&3u32.to_le_bytes());
+        data.extend_from_slice(&500u128.to_le_bytes());
+        data.extend_from_slice(accounts(1).as_bytes());
+        assert_eq!(
+            claim_leaf_hash(3, &accounts(1), 500),
+            env::sha256_array(&data)
+        );
+        assert_ne!(
+            claim_leaf_hash(3, &accounts(1), 500),
+            claim_leaf_hash(4, &accounts(1), 500)
+        );
+    }
+
+    #[test]
+    fn test_claim_with_proof() {
+        let mut contract = setup_contract();
+        let leaf_a = claim_leaf_hash(0, &accounts(1), 500);
+        let leaf_b = claim_leaf_hash(1, &accounts(2), 700);
+        let root = Base58CryptoHash::from(merkle_root(leaf_a, leaf_b));
+        let pool_amount = contract.get_raffle_pool().amount.0;
+
+        setup_context(accounts(0), NearToken::from_yoctonear(1), 0);
+        contract.set_claim_root(1, 0, root, U128(1_200));
+        assert_eq!(contract.get_raffle_pool().amount.0, pool_amount - 1_200);
+        assert_eq!(
+            contract.get_escrow_status().claim_root_escrow_amount.0,
+            1_200
+        );
+
+        setup_context(accounts(1), storage_deposit(), 0);
+        contract.claim_with_proof(1, 0, 0, U128(500), vec![leaf_b.into()]);
+        assert_eq!(contract.ft_balance_of(accounts(1)).0, 500);
+        assert!(contract.is_claimed(1, 0, 0));
+        assert!(!contract.is_claimed(1, 0, 1));
+
+        setup_context(accounts(2), storage_deposit(), 0);
+        contract.claim_with_proof(1, 0, 1, U128(700), vec![leaf_a.into()]);
+        assert_eq!(contract.ft_balance_of(accounts(2)).0, 700);
+        assert_eq!(contract.get_claim_root(1, 0).unwrap().claimed.0, 1_200);
+        assert_eq!(contract.get_raffle_pool().amount.0, pool_amount - 1_200);
+        assert_eq!(contract.get_escrow_status().claim_root_escrow_amount.0, 0);
+        assert!(contract.get_escrow_status().is_solvent);
+    }
+
+    #[test]
+    fn test_replacing_claim_root_releases_reservation() {
+        let mut contract = setup_contract();
+        let leaf_a = claim_leaf_hash(0, &accounts(1), 500);
+        let leaf_b = claim_leaf_hash(1, &accounts(2), 700);
+        let pool_amount = contract.get_raffle_pool().amount.0;
+
+        setup_context(accounts(0), NearToken::from_yoctonear(1), 0);
+        let root = Base58CryptoHash::from(merkle_root(leaf_a, leaf_b));
+        contract.set_claim_root(1, 0, root, U128(1_200));
+        contract.set_claim_root(1, 0, leaf_a.into(), U128(500));
+
+        assert_eq!(contract.get_raffle_pool().amount.0, pool_amount - 500);
+        assert_eq!(contract.get_escrow_status().claim_root_escrow_amount.0, 500);
+    }
+
+    #[test]
+    #[should_panic(expected = "Insufficient funds in pool 1")]
+    fn test_claim_root_reservation_cannot_be_spent_twice() {
+        let mut contract = setup_contract();
+        let pool_amount = contract.get_raffle_pool().amount.0;
+        let leaf = claim_leaf_hash(0, &accounts(1), pool_amount);
+
+        setup_context(accounts(0), NearToken::from_yoctonear(1), 0);
+        contract.set_claim_root(1, 0, leaf.into(), U128(pool_amount));
+        setup_context(accounts(0), storage_deposit(), 0);
+        contract.claim_rewards(U128(1), 1, accounts(2));
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid Merkle proof")]
+    fn test_claim_with_proof_rejects_wrong_amount() {
+        let mut contract = setup_contract();
+        let leaf_a = claim_leaf_hash(0, &accounts(1), 500);
+        let leaf_b = claim_leaf_hash(1, &accounts(2), 700);
+        let root = Base58CryptoHash::from(merkle_root(leaf_a, leaf_b));
+
+        setup_context(accounts(0), NearToken::from_yoctonear(1), 0);
+        contract.set_claim_root(1, 0, root, U128(1_200));
+
+        setup_context(accounts(1), storage_deposit(), 0);
+        contract.claim_with_proof(1, 0, 0, U128(700), vec![leaf_b.into()]);
+    }
+
+    const SIGNER_SEED: [u8; 32] = [7; 32];
+
+    /// near-sdk form of the test signer's ed25519 key.
+    fn signer_public_key(seed: [u8; 32]) -> PublicKey {
+        let mut bytes = vec![0u8];
+        bytes.extend_from_slice(SigningKey::from_bytes(&seed).verifying_key().as_bytes());
+        PublicKey::try_from(bytes).unwrap()
+    }
+
+    fn sign(seed: [u8; 32], message: &[u8]) -> Base64VecU8 {
+        Base64VecU8(SigningKey::from_bytes(&seed).sign(message).to_vec())
+    }
+
+    fn setup_voucher_contract() -> Contract {
+        let mut contract = setup_contract();
+        setup_context(accounts(0), NearToken::from_yoctonear(1), 0);
+        let action_id = contract.set_voucher_signer(Some(signer_public_key(SIGNER_SEED)));
+        execute_timelocked(&mut contract, action_id);
+        contract
+    }
+
+    fn voucher(nonce: u64) -> RewardVoucher {
+        RewardVoucher {
+            pool_id: LOOT_RAFFLE_POOL_ID,
+            recipient: accounts(1),
+            amount: U128(TOKEN_UNIT),
+            nonce: U64(nonce),
+            expires_at: U64(EMISSION_EPOCH_NS),
+        }
+    }
+
+    fn sign_voucher(seed: [u8; 32], voucher: &RewardVoucher) -> Base64VecU8 {
+        sign(seed, &borsh::to_vec(&(accounts(5), voucher)).unwrap())
+    }
+
+    #[test]
+    fn test_redeem_voucher() {
+        let mut contract = setup_voucher_contract();
+        let voucher = voucher(1);
+        let signature = sign_voucher(SIGNER_SEED, &voucher);
+
+        setup_context(accounts(1), storage_deposit(), 0);
+        contract.redeem_voucher(voucher, signature);
+
+        assert_eq!(contract.ft_balance_of(accounts(1)).0, TOKEN_UNIT);
+        assert!(contract.is_voucher_redeemed(U64(1)));
+        assert_eq!(contract.get_user_rewards(accounts(1)).claim_count, 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid voucher signature")]
+    fn test_redeem_voucher_rejects_other_signer() {
+        let mut contract = setup_voucher_contract();
+        let voucher = voucher(1);
+        let signature = sign_voucher([8; 32], &voucher);
+
+        setup_context(accounts(1), storage_deposit(), 0);
+        contract.redeem_voucher(voucher, signature);
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid voucher signature")]
+    fn test_redeem_voucher_rejects_altered_amount() {
+        let mut contract = setup_voucher_contract();
+        let mut voucher = voucher(1);
+        let signature = sign_voucher(SIGNER_SEED, &voucher);
+        voucher.amount = U128(2 * TOKEN_UNIT);
+
+        setup_context(accounts(1), storage_deposit(), 0);
+        contract.redeem_voucher(voucher, signature);
+    }
+
+    #[test]
+    #[should_panic(expected = "Voucher 1 has already been redeemed")]
+    fn test_redeem_voucher_rejects_nonce_replay() {
+        let mut contract = setup_voucher_contract();
+        let first = voucher(1);
+        let first_signature = sign_voucher(SIGNER_SEED, &first);
+        // A fresh voucher that reuses the nonce is refused too
+        let mut second = voucher(1);
+        second.amount = U128(2 * TOKEN_UNIT);
+        let second_signature = sign_voucher(SIGNER_SEED, &second);
+
+        setup_context(accounts(1), storage_deposit(), 0);
+        contract.redeem_voucher(first, first_signature);
+        contract.redeem_voucher(second, second_signature);
+    }
+
+    #[test]
+    #[should_panic(expected = "Attached deposit must cover")]
+    fn test_redeem_voucher_charges_claim_records() {
+        let mut contract = setup_voucher_contract();
+        let voucher = voucher(1);
+        let signature = sign_voucher(SIGNER_SEED, &voucher);
+
+        // Enough for registration and the nonce record, but not for the
+        // claim history the redemption writes afterwards
+        let deposit = contract
+            .storage_balance_bounds()
+            .min
+            .saturating_add(env::storage_byte_cost().saturating_mul(100));
+        setup_context(accounts(1), deposit, 0);
+        contract.redeem_voucher(voucher, signature);
+    }
+
+    #[test]
+    fn test_claim_history() {
+        let mut contract = setup_contract();
+        register(&mut contract, accounts(1));
+        register(&mut contract, accounts(2));
+
+        setup_context(accounts(0), NearToken::from_yoctonear(1), 5);
+        contract.claim_rewards(U128(100), LOOT_RAFFLE_POOL_ID, accounts(1));
+        contract.claim_rewards(U128(200), GLOBAL_TAPPING_POOL_ID, accounts(2));
+        contract.claim_rewards_batch(GLOBAL_TAPPING_POOL_ID, vec![(accounts(1), U128(300))]);
+
+        let stats = contract.get_user_rewards(accounts(1));
+        assert_eq!(stats.total_claimed.0, 400);
+        assert_eq!(stats.claim_count, 2);
+        assert_eq!(stats.last_claim_at.0, 5);
+        let per_pool: Vec<(u32, u128)> = stats
+            .per_pool
+            .iter()
+            .map(|total| (total.pool_id, total.total_claimed.0))
+            .collect();
+        assert_eq!(
+            per_pool,
+            vec![(LOOT_RAFFLE_POOL_ID, 100), (GLOBAL_TAPPING_POOL_ID, 300)]
+        );
+        assert_eq!(contract.get_user_rewards(accounts(3)).claim_count, 0);
+
+        // Newest first, optionally filtered to one account
+        let recent: Vec<(u64, u128, ClaimSource)> = contract
+            .get_recent_claims(None, Some(2))
+            .iter()
+            .map(|record| (record.seq.0, record.amount.0, record.source))
+            .collect();
+        assert_eq!(
+            recent,
+            vec![
+                (2, 300, ClaimSource::Batch),
+                (1, 200, ClaimSource::Distributor)
+            ]
+        );
+        let for_account = contract.get_recent_claims(Some(accounts(1)), None);
+        assert_eq!(for_account.len(), 2);
+        assert_eq!(for_account[1].seq.0, 0);
+    }
+
+    #[test]
+    fn test_recent_claims_keep_the_latest_256() {
+        let mut contract = setup_contract();
+        register(&mut contract, accounts(1));
+
+        for _ in 0..300 {
+            setup_context(accounts(0), NearToken::from_yoctonear(1), 0);
+            contract.claim_rewards(U128(1), LOOT_RAFFLE_POOL_ID, accounts(1));
+        }
+
+        let recent = contract.get_recent_claims(None, None);
+        assert_eq!(recent.len(), 256);
+        assert_eq!(recent[0].seq.0, 299);
+        assert_eq!(recent[255].seq.0, 44);
+        assert_eq!(contract.get_user_rewards(accounts(1)).claim_count, 300);
+    }
+
+    #[test]
+    #[should_panic(expected = "Claim of 101 exceeds the per-claim limit of 100 for pool 1")]
+    fn test_per_claim_limit() {
+        let mut contract = setup_contract();
+        set_claim_limits(
+            &mut contract,
+            LOOT_RAFFLE_POOL_ID,
+            ClaimLimits {
+                max_per_claim: Some(U128(100)),
+                ..Default::default()
+            },
+        );
+
+        setup_context(accounts(0), storage_deposit(), 0);
+        contract.claim_rewards(U128(100), LOOT_RAFFLE_POOL_ID, accounts(1));
+        contract.claim_rewards(U128(101), LOOT_RAFFLE_POOL_ID, accounts(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "Remaining allowance for bob in epoch 0: 50")]
+    fn test_per_user_epoch_limit() {
+        let mut contract = setup_contract();
+        set_claim_limits(
+            &mut contract,
+            LOOT_RAFFLE_POOL_ID,
+            ClaimLimits {
+                max_per_user_per_epoch: Some(U128(200)),
+                ..Default::default()
+            },
+        );
+
+        setup_context(accounts(0), storage_deposit(), 0);
+        contract.claim_rewards(U128(150), LOOT_RAFFLE_POOL_ID, accounts(1));
+        // Other accounts have their own allowance
+        contract.claim_rewards(U128(200), LOOT_RAFFLE_POOL_ID, accounts(2));
+        let allowance = contract.get_claim_allowance(LOOT_RAFFLE_POOL_ID, accounts(1));
+        assert_eq!(allowance.user_remaining, Some(U128(50)));
+
+        contract.claim_rewards(U128(51), LOOT_RAFFLE_POOL_ID, accounts(1));
+    }
+
+    #[test]
+    fn test_pool_epoch_limit_resets_on_mint() {
+        let mut contract = setup_contract();
+        set_claim_limits(
+            &mut contract,
+            LOOT_RAFFLE_POOL_ID,
+            ClaimLimits {
+                max_per_epoch: Some(U128(300)),
+                ..Default::default()
+            },
+        );
+
+        setup_context(accounts(0), storage_deposit(), 0);
+        contract.claim_rewards(U128(200), LOOT_RAFFLE_POOL_ID, accounts(1));
+        contract.claim_rewards(U128(100), LOOT_RAFFLE_POOL_ID, accounts(2));
+        let allowance = contract.get_claim_allowance(LOOT_RAFFLE_POOL_ID, accounts(3));
+        assert_eq!(
+            (allowance.epoch, allowance.pool_remaining),
+            (0, Some(U128(0)))
+        );
+
+        setup_context(accounts(0), NearToken::from_yoctonear(1), 0);
+        contract.mint(None);
+        let allowance = contract.get_claim_allowance(LOOT_RAFFLE_POOL_ID, accounts(3));
+        assert_eq!(
+            (allowance.epoch, allowance.pool_remaining),
+            (1, Some(U128(300)))
+        );
+
+        setup_context(accounts(0), storage_deposit(), 0);
+        contract.claim_rewards(U128(300), LOOT_RAFFLE_POOL_ID, accounts(3));
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "exceeds the epoch limit for pool 1. Remaining allowance in epoch 0: 100"
+    )]
+    fn test_pool_epoch_limit() {
+        let mut contract = setup_contract();
+        set_claim_limits(
+            &mut contract,
+            LOOT_RAFFLE_POOL_ID,
+            ClaimLimits {
+                max_per_epoch: Some(U128(300)),
+                ..Default::default()
+            },
+        );
+
+        setup_context(accounts(0), storage_deposit(), 0);
+        contract.claim_rewards(U128(200), LOOT_RAFFLE_POOL_ID, accounts(1));
+        contract.claim_rewards(U128(101), LOOT_RAFFLE_POOL_ID, accounts(2));
+    }
+
+    const TAPPING_REFILL: u128 = 1_000 * TOKEN_UNIT;
+
+    /// Put the tapping pool on a daily schedule and fund its reserve with a
+    /// mint. Returns the schedule's anchor.
+    fn setup_tapping_schedule(contract: &mut Contract, remainder: RemainderPolicy) -> u64 {
+        setup_context(accounts(0), NearToken::from_yoctonear(1), 0);
+        let action_id = contract.set_tapping_refill_schedule(Some(TappingRefillConfig {
+            period: RefillPeriod::Daily,
+            amount: U128(TAPPING_REFILL),
+            remainder,
+        }));
+        execute_timelocked(contract, action_id);
+        let anchor = contract.get_tapping_refill_schedule().unwrap().anchor.0;
+
+        setup_context(accounts(0), NearToken::from_yoctonear(1), anchor);
+        contract.mint(None);
+        assert_eq!(
+            contract.get_escrow_status().tapping_reserve_amount.0,
+            30 * TAPPING_REFILL
+        );
+        anchor
+    }
+
+    /// Apply the first scheduled refill and return the remainder it replaced.
+    fn refill_next_day(contract: &mut Contract, anchor: u64) -> u128 {
+        let remainder = contract.get_tapping_pool().amount.0;
+        setup_context(
+            accounts(1),
+            NearToken::from_yoctonear(0),
+            anchor + RefillPeriod::Daily.duration_ns(),
+        );
+        contract.refill_tapping_pool();
+        assert_eq!(
+            contract.get_escrow_status().tapping_reserve_amount.0,
+            29 * TAPPING_REFILL
+        );
+        assert!(contract.get_escrow_status().is_solvent);
+        remainder
+    }
+
+    #[test]
+    fn test_tapping_refill_rollover() {
+        let mut contract = setup_contract();
+        let anchor = setup_tapping_schedule(&mut contract, RemainderPolicy::Rollover);
+        let supply = contract.ft_total_supply().0;
+
+        let remainder = refill_next_day(&mut contract, anchor);
+
+        assert_eq!(
+            contract.get_tapping_pool().amount.0,
+            remainder + TAPPING_REFILL
+        );
+        assert_eq!(contract.ft_total_supply().0, supply);
+    }
+
+    #[test]
+    fn test_tapping_refill_burn() {
+        let mut contract = setup_contract();
+        let anchor = setup_tapping_schedule(&mut contract, RemainderPolicy::Burn);
+        let supply = contract.ft_total_supply().0;
+
+        let remainder = refill_next_day(&mut contract, anchor);
+
+        assert_eq!(contract.get_tapping_pool().amount.0, TAPPING_REFILL);
+        assert_eq!(contract.ft_total_supply().0, supply - remainder);
+    }
+
+    #[test]
+    fn test_tapping_refill_return_to_treasury() {
+        let mut contract = setup_contract();
+        let anchor = setup_tapping_schedule(&mut contract, RemainderPolicy::ReturnToTreasury);
+        let owner_balance = contract.ft_balance_of(accounts(0)).0;
+
+        let remainder = refill_next_day(&mut contract, anchor);
+
+        assert_eq!(contract.get_tapping_pool().amount.0, TAPPING_REFILL);
+        assert_eq!(
+            contract.ft_balance_of(accounts(0)).0,
+            owner_balance + remainder
+        );
+    }
+
+    #[test]
+    fn test_tapping_refill_waits_for_next_period() {
+        let mut contract = setup_contract();
+        let anchor = setup_tapping_schedule(&mut contract, RemainderPolicy::Burn);
+        let pool_amount = contract.get_tapping_pool().amount.0;
+
+        setup_context(accounts(1), NearToken::from_yoctonear(0), anchor + 1);
+        contract.refill_tapping_pool();
+
+        assert_eq!(contract.get_tapping_pool().amount.0, pool_amount);
+        assert_eq!(
+            contract.get_next_tapping_refill_time().unwrap().0,
+            anchor + RefillPeriod::Daily.duration_ns()
+        );
+    }
+
+    const TICKET_PRICE: u128 = 10 * TOKEN_UNIT;
+    const RAFFLE_CLOSE_HEIGHT: u64 = 100;
+
+    fn setup_raffle_context(predecessor: AccountId, deposit: NearToken, block_height: u64) {
+        let mut builder = VMContextBuilder::new();
+        builder
+            .current_account_id(accounts(5))
+            .predecessor_account_id(predecessor)
+            .attached_deposit(deposit)
+            .block_height(block_height)
+            .random_seed([block_height as u8; 32]);
+        testing_env!(builder.build());
+    }
+
+    /// Open a two-prize round and have `accounts(1)` buy 3 tickets and
+    /// `accounts(2)` buy 1.
+    fn setup_raffle(contract: &mut Contract) -> u64 {
+        setup_context(accounts(0), storage_deposit(), 0);
+        contract.claim_rewards(U128(100 * TOKEN_UNIT), LOOT_RAFFLE_POOL_ID, accounts(1));
+        contract.claim_rewards(U128(100 * TOKEN_UNIT), LOOT_RAFFLE_POOL_ID, accounts(2));
+
+        setup_raffle_context(accounts(0), NearToken::from_yoctonear(1), 1);
+        let round_id = contract.create_raffle_round(
+            U128(TICKET_PRICE),
+            vec![U128(300 * TOKEN_UNIT), U128(200 * TOKEN_UNIT)],
+            U64(RAFFLE_CLOSE_HEIGHT),
+        );
+        for (account_id, tickets) in [(accounts(1), 3), (accounts(2), 1)] {
+            setup_raffle_context(account_id.clone(), storage_deposit(), 2);
+            contract.enter_raffle(round_id, U64(tickets));
+        }
+        round_id
+    }
+
+    #[test]
+    fn test_draw_raffle() {
+        let mut contract = setup_contract();
+        let pool_amount = contract.get_raffle_pool().amount.0;
+        let round_id = setup_raffle(&mut contract);
+        assert_eq!(
+            contract.get_raffle_pool().amount.0,
+            pool_amount - 200 * TOKEN_UNIT - 500 * TOKEN_UNIT
+        );
+        assert_eq!(contract.get_raffle_tickets(round_id, accounts(1)).0, 3);
+        assert_eq!(
+            contract.get_escrow_status().raffle_escrow_amount.0,
+            500 * TOKEN_UNIT + 4 * TICKET_PRICE
+        );
+        let supply = contract.ft_total_supply().0;
+        let balances = [accounts(1), accounts(2)].map(|id| contract.ft_balance_of(id).0);
+
+        setup_raffle_context(
+            accounts(3),
+            NearToken::from_yoctonear(0),
+            RAFFLE_CLOSE_HEIGHT + 1,
+        );
+        contract.draw_raffle(round_id);
+
+        let round = contract.get_raffle_round(round_id).unwrap();
+        assert_eq!(round.status, RaffleStatus::Drawn);
+        assert_eq!(round.drawn_at_height, Some(U64(RAFFLE_CLOSE_HEIGHT + 1)));
+        let winners = contract.get_raffle_winners(round_id);
+        assert_eq!(winners.len(), 2);
+        assert_ne!(winners[0].ticket, winners[1].ticket);
+        for winner in &winners {
+            // Tickets 0-2 belong to accounts(1) and ticket 3 to accounts(2)
+            let owner = if winner.ticket.0 < 3 {
+                accounts(1)
+            } else {
+                accounts(2)
+            };
+            assert_eq!(winner.account_id, owner);
+            assert!(winner.paid);
+        }
+        for (index, account_id) in [accounts(1), accounts(2)].into_iter().enumerate() {
+            let won: u128 = winners
+                .iter()
+                .filter(|winner| winner.account_id == account_id)
+                .map(|winner| winner.prize.0)
+                .sum();
+            assert_eq!(contract.ft_balance_of(account_id).0, balances[index] + won);
+        }
+
+        // Prizes were already out of supply; the ticket payments are burned
+        assert_eq!(contract.ft_total_supply().0, supply - 4 * TICKET_PRICE);
+        assert_eq!(contract.get_escrow_status().raffle_escrow_amount.0, 0);
+        assert!(contract.get_escrow_status().is_solvent);
+    }
+
+    #[test]
+    #[should_panic(expected = "Raffle round 0 closes at block 100")]
+    fn test_draw_raffle_before_close() {
+        let mut contract = setup_contract();
+        let round_id = setup_raffle(&mut contract);
+
+        setup_raffle_context(
+            accounts(3),
+            NearToken::from_yoctonear(0),
+            RAFFLE_CLOSE_HEIGHT,
+        );
+        contract.draw_raffle(round_id);
+    }
+
+    #[test]
+    #[should_panic(expected = "Raffle round 0 is closed for entries")]
+    fn test_enter_raffle_after_close() {
+        let mut contract = setup_contract();
+        let round_id = setup_raffle(&mut contract);
+
+        setup_raffle_context(accounts(1), storage_deposit(), RAFFLE_CLOSE_HEIGHT + 1);
+        contract.enter_raffle(round_id, U64(1));
+    }
+
+    const RAFFLE_SECRET: &[u8] = b"raffle secret";
+    const REVEAL_DEADLINE: u64 = RAFFLE_CLOSE_HEIGHT + 50;
+
+    fn setup_committed_raffle(contract: &mut Contract) -> u64 {
+        let round_id = setup_raffle(contract);
+        setup_raffle_context(accounts(0), NearToken::from_yoctonear(1), 3);
+        let commitment = Base58CryptoHash::from(env::sha256_array(RAFFLE_SECRET));
+        contract.commit_raffle_secret(round_id, commitment, U64(REVEAL_DEADLINE));
+        round_id
+    }
+
+    #[test]
+    fn test_reveal_raffle() {
+        let mut contract = setup_contract();
+        let round_id = setup_committed_raffle(&mut contract);
+
+        setup_raffle_context(accounts(0), NearToken::from_yoctonear(1), REVEAL_DEADLINE);
+        contract.reveal_raffle(round_id, Base64VecU8(RAFFLE_SECRET.to_vec()));
+
+        let round = contract.get_raffle_round(round_id).unwrap();
+        assert_eq!(round.status, RaffleStatus::Drawn);
+        assert_eq!(round.winners.len(), 2);
+        assert_eq!(contract.get_escrow_status().raffle_escrow_amount.0, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Secret does not match the commitment")]
+    fn test_reveal_raffle_rejects_wrong_secret() {
+        let mut contract = setup_contract();
+        let round_id = setup_committed_raffle(&mut contract);
+
+        setup_raffle_context(accounts(0), NearToken::from_yoctonear(1), REVEAL_DEADLINE);
+        contract.reveal_raffle(round_id, Base64VecU8(b"another secret".to_vec()));
+    }
+
+    #[test]
+    #[should_panic(expected = "uses commit-reveal; call reveal_raffle")]
+    fn test_committed_raffle_cannot_be_drawn() {
+        let mut contract = setup_contract();
+        let round_id = setup_committed_raffle(&mut contract);
+
+        setup_raffle_context(
+            accounts(3),
+            NearToken::from_yoctonear(0),
+            RAFFLE_CLOSE_HEIGHT + 1,
+        );
+        contract.draw_raffle(round_id);
+    }
+
+    #[test]
+    #[should_panic(expected = "can still be revealed until block 150")]
+    fn test_expire_raffle_before_deadline() {
+        let mut contract = setup_contract();
+        let round_id = setup_committed_raffle(&mut contract);
+
+        setup_raffle_context(accounts(3), NearToken::from_yoctonear(0), REVEAL_DEADLINE);
+        contract.expire_raffle(round_id);
+    }
+
+    #[test]
+    fn test_unrevealed_raffle_refunds_tickets() {
+        let mut contract = setup_contract();
+        let pool_amount = contract.get_raffle_pool().amount.0;
+        let round_id = setup_committed_raffle(&mut contract);
+        let balance = contract.ft_balance_of(accounts(1)).0;
+
+        // The first refund expires the round and returns the prizes
+        setup_raffle_context(
+            accounts(1),
+            NearToken::from_yoctonear(0),
+            REVEAL_DEADLINE + 1,
+        );
+        contract.claim_raffle_refund(round_id);
+        assert_eq!(
+            contract.get_raffle_round(round_id).unwrap().status,
+            RaffleStatus::Refunded
+        );
+        assert_eq!(
+            contract.ft_balance_of(accounts(1)).0,
+            balance + 3 * TICKET_PRICE
+        );
+        assert_eq!(
+            contract.get_raffle_pool().amount.0,
+            pool_amount - 200 * TOKEN_UNIT
+        );
+
+        setup_raffle_context(
+            accounts(2),
+            NearToken::from_yoctonear(0),
+            REVEAL_DEADLINE + 1,
+        );
+        contract.claim_raffle_refund(round_id);
+        assert_eq!(contract.get_escrow_status().raffle_escrow_amount.0, 0);
+        assert!(contract.get_escrow_status().is_solvent);
+    }
+
+    #[test]
+    #[should_panic(expected = "has no tickets to refund in raffle round 0")]
+    fn test_raffle_refund_claimed_once() {
+        let mut contract = setup_contract();
+        let round_id = setup_committed_raffle(&mut contract);
+
+        setup_raffle_context(
+            accounts(1),
+            NearToken::from_yoctonear(0),
+            REVEAL_DEADLINE + 1,
+        );
+        contract.claim_raffle_refund(round_id);
+        contract.claim_raffle_refund(round_id);
+    }
+
+    const TAP_SIGNER_SEED: [u8; 32] = [9; 32];
+
+    fn setup_taps_contract() -> Contract {
+        let mut contract = setup_contract();
+        setup_context(accounts(0), NearToken::from_yoctonear(1), 0);
+        let action_id = contract.set_tap_signer(Some(signer_public_key(TAP_SIGNER_SEED)));
+        execute_timelocked(&mut contract, action_id);
+        contract
+    }
+
+    fn tap_report(seed: [u8; 32], account_id: AccountId, taps: u64) -> TapReport {
+        let message = borsh::to_vec(&(accounts(5), "taps", 0u64, &account_id, taps)).unwrap();
+        TapReport {
+            account_id,
+            taps: U64(taps),
+            signature: sign(seed, &message),
+        }
+    }
+
+    fn submit_taps(contract: &mut Contract, reports: Vec<TapReport>) {
+        setup_context(accounts(0), storage_deposit(), 0);
+        contract.submit_taps(0, reports);
+    }
+
+    #[test]
+    fn test_submit_taps_counts_only_the_increase() {
+        let mut contract = setup_taps_contract();
+        let weight = |contract: &Contract, account_id| {
+            contract
+                .get_epoch_weight(GLOBAL_TAPPING_POOL_ID, 0, account_id)
+                .0
+        };
+
+        submit_taps(
+            &mut contract,
+            vec![
+                tap_report(TAP_SIGNER_SEED, accounts(1), 10),
+                tap_report(TAP_SIGNER_SEED, accounts(2), 4),
+            ],
+        );
+        assert_eq!(weight(&contract, accounts(1)), 10);
+        assert_eq!(weight(&contract, accounts(2)), 4);
+
+        // Resubmitting a running total does not count it twice
+        submit_taps(
+            &mut contract,
+            vec![tap_report(TAP_SIGNER_SEED, accounts(1), 10)],
+        );
+        assert_eq!(weight(&contract, accounts(1)), 10);
+        submit_taps(
+            &mut contract,
+            vec![tap_report(TAP_SIGNER_SEED, accounts(1), 15)],
+        );
+        assert_eq!(weight(&contract, accounts(1)), 15);
+        // A lower total is ignored
+        submit_taps(
+            &mut contract,
+            vec![tap_report(TAP_SIGNER_SEED, accounts(1), 12)],
+        );
+        assert_eq!(weight(&contract, accounts(1)), 15);
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid tap report signature for bob")]
+    fn test_submit_taps_rejects_voucher_signer() {
+        let mut contract = setup_taps_contract();
+        setup_context(accounts(0), NearToken::from_yoctonear(1), 0);
+        let action_id = contract.set_voucher_signer(Some(signer_public_key(SIGNER_SEED)));
+        execute_timelocked(&mut contract, action_id);
+
+        submit_taps(
+            &mut contract,
+            vec![tap_report(SIGNER_SEED, accounts(1), 10)],
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid tap report signature for bob")]
+    fn test_submit_taps_requires_domain_tag() {
+        let mut contract = setup_taps_contract();
+        let message = borsh::to_vec(&(accounts(5), 0u64, &accounts(1), 10u64)).unwrap();
+        let report = TapReport {
+            account_id: accounts(1),
+            taps: U64(10),
+            signature: sign(TAP_SIGNER_SEED, &message),
+        };
+
+        submit_taps(&mut contract, vec![report]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Tap submissions are disabled")]
+    fn test_submit_taps_without_tap_signer() {
+        let mut contract = setup_contract();
+        submit_taps(
+            &mut contract,
+            vec![tap_report(TAP_SIGNER_SEED, accounts(1), 10)],
+        );
+    }
+
+    #[test]
+    fn test_epoch_distribution_pro_rata_and_dust() {
+        let mut contract = setup_contract();
+        let reward = contract.get_tapping_pool().amount.0; // 10^14, not divisible by 3
+        contract.internal_accrue_weight(2, &accounts(1), 1);
+        contract.internal_accrue_weight(2, &accounts(2), 1);
+        contract.internal_accrue_weight(2, &accounts(3), 1);
+
+        setup_context(accounts(0), NearToken::from_yoctonear(1), 0);
+        contract.finalize_epoch(2);
+        let distribution = contract.get_epoch_distribution(2, 0).unwrap();
+        assert_eq!(distribution.reward.0, reward);
+        assert_eq!(distribution.total_weight.0, 3);
+        assert_eq!(contract.get_tapping_pool().amount.0, 0);
+        assert_eq!(contract.get_current_distribution_epoch(2), 1);
+
+        let share = reward / 3;
+        for account_id in [accounts(1), accounts(2), accounts(3)] {
+            assert_eq!(
+                contract
+                    .get_claimable_epoch_rewards(2, 0, account_id.clone())
+                    .0,
+                share
+            );
+            setup_context(account_id.clone(), storage_deposit(), 0);
+            assert_eq!(contract.claim_epoch_rewards(2, 0).0, share);
+            assert_eq!(contract.ft_balance_of(account_id).0, share);
+        }
+
+        // The rounding dust goes back to the pool once all weight has claimed
+        assert_eq!(contract.get_tapping_pool().amount.0, reward - 3 * share);
+        assert_eq!(contract.get_escrow_status().distribution_escrow_amount.0, 0);
+        assert!(contract.get_escrow_status().is_solvent);
+    }
+
+    #[test]
+    fn test_close_epoch_distribution_returns_unclaimed_reward() {
+        let mut contract = setup_contract();
+        let reward = contract.get_tapping_pool().amount.0;
+        contract.internal_accrue_weight(2, &accounts(1), 1);
+        contract.internal_accrue_weight(2, &accounts(2), 2);
+        setup_context(accounts(0), NearToken::from_yoctonear(1), 0);
+        contract.finalize_epoch(2);
+
+        setup_context(accounts(1), storage_deposit(), 0);
+        let claimed = contract.claim_epoch_rewards(2, 0).0;
+        assert_eq!(claimed, reward / 3);
+
+        setup_context(accounts(0), NearToken::from_yoctonear(1), 0);
+        for _ in 0..EPOCH_CLAIM_WINDOW {
+            contract.finalize_epoch(2);
+        }
+        assert_eq!(
+            contract.get_current_distribution_epoch(2),
+            EPOCH_CLAIM_WINDOW + 1
+        );
+
+        setup_context(accounts(3), NearToken::from_yoctonear(0), 0);
+        assert_eq!(contract.close_epoch_distribution(2, 0).0, reward - claimed);
+        assert!(contract
+            .get_epoch_distribution(2, 0)
+            .unwrap()
+            .closed_at
+            .is_some());
+        assert_eq!(contract.get_tapping_pool().amount.0, reward - claimed);
+        assert_eq!(contract.get_escrow_status().distribution_escrow_amount.0, 0);
+        assert_eq!(contract.get_claimable_epoch_rewards(2, 0, accounts(2)).0, 0);
+        assert!(contract.get_escrow_status().is_solvent);
+    }
+
+    #[test]
+    #[should_panic(expected = "Epoch 0 of pool 2 can be claimed until epoch 4 opens")]
+    fn test_close_epoch_distribution_within_claim_window() {
+        let mut contract = setup_contract();
+        contract.internal_accrue_weight(2, &accounts(1), 1);
+        setup_context(accounts(0), NearToken::from_yoctonear(1), 0);
+        for _ in 0..EPOCH_CLAIM_WINDOW {
+            contract.finalize_epoch(2);
+        }
+        contract.close_epoch_distribution(2, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "The claim window for epoch 0 of pool 2 has closed")]
+    fn test_claim_epoch_rewards_after_close() {
+        let mut contract = setup_contract();
+        contract.internal_accrue_weight(2, &accounts(1), 1);
+        setup_context(accounts(0), NearToken::from_yoctonear(1), 0);
+        for _ in 0..=EPOCH_CLAIM_WINDOW {
+            contract.finalize_epoch(2);
+        }
+        contract.close_epoch_distribution(2, 0);
+
+        setup_context(accounts(1), storage_deposit(), 0);
+        contract.claim_epoch_rewards(2, 0);
+    }
+
+    #[test]
+    fn test_staking_accumulator_streams_rewards() {
+        let mut contract = setup_contract();
+        contract.token.internal_deposit(&accounts(5), 2_000);
+
+        for account_id in [accounts(1), accounts(2)] {
+            setup_context(account_id, storage_deposit(), 0);
+            contract.stake_storage_deposit();
+        }
+
+        // accounts(1) stakes before the rewards are funded
+        setup_context(accounts(5), NearToken::from_yoctonear(0), 0);
+        contract.ft_on_transfer(accounts(1), U128(1_000), "stake".to_string());
+        contract.internal_fund_staking_rewards(1_000, 1);
+        assert_eq!(contract.get_stake(accounts(1)).pending_rewards.0, 0);
+
+        // Half the stream has been released when accounts(2) joins
+        setup_context(
+            accounts(5),
+            NearToken::from_yoctonear(0),
+            EMISSION_EPOCH_NS / 2,
+        );
+        assert_eq!(contract.get_stake(accounts(1)).pending_rewards.0, 500);
+        contract.ft_on_transfer(accounts(2), U128(1_000), "stake".to_string());
+
+        // The second half is split between both stakes
+        setup_context(accounts(5), NearToken::from_yoctonear(0), EMISSION_EPOCH_NS);
+        assert_eq!(contract.get_stake(accounts(1)).pending_rewards.0, 750);
+        assert_eq!(contract.get_stake(accounts(2)).pending_rewards.0, 250);
+
+        contract.token.internal_register_account(&accounts(1));
+        setup_context(accounts(1), NearToken::from_yoctonear(1), EMISSION_EPOCH_NS);
+        assert_eq!(contract.claim_staking_rewards().0, 750);
+        let state = contract.get_staking_state();
+        assert_eq!(state.streaming_rewards.0, 0);
+        assert_eq!(state.reward_escrow.0, 250);
+    }
+    #[test]
+    #[should_panic(expected = "Call stake_storage_deposit before staking")]
+    fn test_stake_without_storage_deposit() {
+        let mut contract = setup_contract();
+        contract.token.internal_deposit(&accounts(5), 1_000);
+        setup_context(accounts(5), NearToken::from_yoctonear(0), 0);
+        contract.ft_on_transfer(accounts(1), U128(1_000), "stake".to_string());
+    }
+
+    #[test]
+    fn test_stake_storage_is_kept_until_unregistered() {
+        let mut contract = setup_contract();
+        contract.token.internal_deposit(&accounts(5), 1_000);
+        contract.token.internal_register_account(&accounts(1));
+        setup_context(accounts(1), storage_deposit(), 0);
+        contract.stake_storage_deposit();
+        assert!(contract.get_stake(accounts(1)).storage_paid);
+
+        setup_context(accounts(5), NearToken::from_yoctonear(0), 0);
+        contract.ft_on_transfer(accounts(1), U128(1_000), "stake".to_string());
+        setup_context(accounts(1), NearToken::from_yoctonear(1), 0);
+        contract.unstake(U128(1_000));
+        assert_eq!(contract.ft_balance_of(accounts(1)).0, 1_000);
+
+        // The emptied entry is still paid for, so staking again needs no deposit
+        assert!(contract.get_stake(accounts(1)).storage_paid);
+        contract.stake_storage_unregister();
+        assert!(!contract.get_stake(accounts(1)).storage_paid);
+    }
+
+    #[test]
+    #[should_panic(expected = "Unstake, withdraw and claim everything before unregistering")]
+    fn test_stake_storage_unregister_with_stake() {
+        let mut contract = setup_contract();
+        contract.token.internal_deposit(&accounts(5), 1_000);
+        setup_context(accounts(1), storage_deposit(), 0);
+        contract.stake_storage_deposit();
+        setup_context(accounts(5), NearToken::from_yoctonear(0), 0);
+        contract.ft_on_transfer(accounts(1), U128(1_000), "stake".to_string());
+
+        setup_context(accounts(1), NearToken::from_yoctonear(1), 0);
+        contract.stake_storage_unregister();
+    }
+}
@@ -0,0 +1,271 @@
+use near_sdk::json_types::U128;
+use near_sdk::{env, near, require, AccountId};
+
+use crate::roles::Role;
+use crate::timelock::TimelockAction;
+use crate::{assert_one_yocto, Contract, ContractExt};
+
+/// Caps on what a pool pays out. Each cap is optional; epochs follow the
+/// `mint` cycle, so usage resets whenever a new emission epoch is minted.
+#[near(serializers = [borsh, json])]
+#[derive(Clone, Default)]
+pub struct ClaimLimits {
+    pub max_per_claim: Option<U128>,
+    pub max_per_user_per_epoch: Option<U128>,
+    pub max_per_epoch: Option<U128>,
+}
+
+impl ClaimLimits {
+    fn is_empty(&self) -> bool {
+        self.max_per_claim.is_none()
+            && self.max_per_user_per_epoch.is_none()
+            && self.max_per_epoch.is_none()
+    }
+}
+
+/// Amount claimed within one emission epoch.
+#[near(serializers = [borsh])]
+#[derive(Clone, Copy, Default)]
+pub struct EpochUsage {
+    pub epoch: u32,
+    pub claimed: u128,
+}
+
+impl EpochUsage {
+    fn claimed_in(&self, epoch: u32) -> u128 {
+        if self.epoch == epoch {
+            self.claimed
+        } else {
+            0
+        }
+    }
+}
+
+/// What an account can still claim from a pool in the current epoch.
+/// `None` means the corresponding cap is not set.
+#[near(serializers = [json])]
+pub struct ClaimAllowance {
+    pub epoch: u32,
+    pub max_per_claim: Option<U128>,
+    pub user_remaining: Option<U128>,
+    pub pool_remaining: Option<U128>,
+}
+
+#[near]
+impl Contract {
+    /// Queue new claim caps for a pool. Passing no caps removes them.
+    /// `ConfigAdmin` only. Returns the queued action id.
+    #[payable]
+    pub fn set_pool_claim_limits(&mut self, pool_id: u32, limits: ClaimLimits) -> u64 {
+        assert_one_yocto();
+        self.assert_role(Role::ConfigAdmin);
+        self.internal_pool(pool_id);
+        for cap in [
+            limits.max_per_claim,
+            limits.max_per_user_per_epoch,
+            limits.max_per_epoch,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            require!(cap.0 > 0, "Claim limits must be greater than zero");
+        }
+
+        self.internal_queue_action(TimelockAction::SetPoolClaimLimits { pool_id, limits })
+    }
+
+    /// Claim caps configured for a pool
+    pub fn get_pool_claim_limits(&self, pool_id: u32) -> ClaimLimits {
+        self.pool_claim_limits
+            .get(&pool_id)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Remaining allowance for `account_id` in `pool_id` this epoch
+    pub fn get_claim_allowance(&self, pool_id: u32, account_id: AccountId) -> ClaimAllowance {
+        let limits = self.get_pool_claim_limits(pool_id);
+        let epoch = self.emissions.current_month;
+        let user_claimed = self
+            .user_epoch_claims
+            .get(&(pool_id, account_id))
+            .map_or(0, |usage| usage.claimed_in(epoch));
+        let pool_claimed = self
+            .pool_epoch_claims
+            .get(&pool_id)
+            .map_or(0, |usage| usage.claimed_in(epoch));
+        ClaimAllowance {
+            epoch,
+            max_per_claim: limits.max_per_claim,
+            user_remaining: limits
+                .max_per_user_per_epoch
+                .map(|cap| U128(cap.0.saturating_sub(user_claimed))),
+            pool_remaining: limits
+                .max_per_epoch
+                .map(|cap| U128(cap.0.saturating_sub(pool_claimed))),
+        }
+    }
+}
+
+impl Contract {
+    pub(crate) fn internal_set_pool_claim_limits(&mut self, pool_id: u32, limits: ClaimLimits) {
+        if limits.is_empty() {
+            self.pool_claim_limits.remove(&pool_id);
+        } else {
+            self.pool_claim_limits.insert(pool_id, limits);
+        }
+    }
+
+    /// Count `amount` against the pool's caps, panicking with the remaining
+    /// allowance if any cap would be exceeded.
+    pub(crate) fn internal_consume_claim_allowance(
+        &mut self,
+        pool_id: u32,
+        account_id: &AccountId,
+        amount: u128,
+    ) {
+        if let Err(message) = self.internal_try_consume_claim_allowance(pool_id, account_id, amount)
+        {
+            env::panic_str(&message);
+        }
+    }
+
+    /// Count a payout to recipients not known yet, such as raffle prizes,
+    /// against the pool's per-claim cap (for each amount) and epoch cap (for
+    /// their total). The per-user cap cannot apply and is not checked.
+    pub(crate) fn internal_consume_pool_claim_allowance(&mut self, pool_id: u32, amounts: &[u128]) {
+        let Some(limits) = self.pool_claim_limits.get(&pool_id).cloned() else {
+            return;
+        };
+
+        if let Some(cap) = limits.max_per_claim {
+            for amount in amounts {
+                require!(
+                    *amount <= cap.0,
+                    format!(
+                        "Claim of {} exceeds the per-claim limit of {} for pool {}",
+                        amount, cap.0, pool_id
+                    )
+                );
+            }
+        }
+
+        if let Some(remaining) = self.internal_pool_epoch_remaining(pool_id) {
+            let total = amounts
+                .iter()
+                .try_fold(0u128, |total, amount| total.checked_add(*amount))
+                .expect("Claim total overflow");
+            require!(
+                total <= remaining,
+                format!(
+                    "Claim of {} exceeds the epoch limit for pool {}. Remaining allowance in epoch {}: {}",
+                    total, pool_id, self.emissions.current_month, remaining
+                )
+            );
+            self.internal_record_pool_epoch_claim(pool_id, total);
+        }
+    }
+
+    /// What the pool's epoch cap still allows in the current emission epoch,
+    /// or `None` if the pool has no epoch cap.
+    pub(crate) fn internal_pool_epoch_remaining(&self, pool_id: u32) -> Option<u128> {
+        let cap = self.pool_claim_limits.get(&pool_id)?.max_per_epoch?;
+        let pool_claimed = self
+            .pool_epoch_claims
+            .get(&pool_id)
+            .map_or(0, |usage| usage.claimed_in(self.emissions.current_month));
+        Some(cap.0.saturating_sub(pool_claimed))
+    }
+
+    /// Count `amount` against the pool's epoch cap, if it has one. Callers
+    /// check the amount fits first.
+    pub(crate) fn internal_record_pool_epoch_claim(&mut self, pool_id: u32, amount: u128) {
+        if self.internal_pool_epoch_remaining(pool_id).is_none() {
+            return;
+        }
+        let epoch = self.emissions.current_month;
+        let pool_claimed = self
+            .pool_epoch_claims
+            .get(&pool_id)
+            .map_or(0, |usage| usage.claimed_in(epoch));
+        self.pool_epoch_claims.insert(
+            pool_id,
+            EpochUsage {
+                epoch,
+                claimed: pool_claimed + amount,
+            },
+        );
+    }
+
+    /// Count `amount` against the pool's caps, or describe which cap it
+    /// would exceed without recording anything.
+    pub(crate) fn internal_try_consume_claim_allowance(
+        &mut self,
+        pool_id: u32,
+        account_id: &AccountId,
+        amount: u128,
+    ) -> Result<(), String> {
+        let Some(limits) = self.pool_claim_limits.get(&pool_id).cloned() else {
+            return Ok(());
+        };
+        let epoch = self.emissions.current_month;
+
+        if let Some(cap) = limits.max_per_claim {
+            if amount > cap.0 {
+                return Err(format!(
+                    "Claim of {} exceeds the per-claim limit of {} for pool {}",
+                    amount, cap.0, pool_id
+                ));
+            }
+        }
+
+        let user_key = (pool_id, account_id.clone());
+        let user_claimed = self
+            .user_epoch_claims
+            .get(&user_key)
+            .map_or(0, |usage| usage.claimed_in(epoch));
+        if let Some(cap) = limits.max_per_user_per_epoch {
+            let remaining = cap.0.saturating_sub(user_claimed);
+            if amount > remaining {
+                return Err(format!(
+                    "Claim of {} exceeds the per-user epoch limit for pool {}. Remaining allowance for {} in epoch {}: {}",
+                    amount, pool_id, account_id, epoch, remaining
+                ));
+            }
+        }
+
+        let pool_claimed = self
+            .pool_epoch_claims
+            .get(&pool_id)
+            .map_or(0, |usage| usage.claimed_in(epoch));
+        if let Some(cap) = limits.max_per_epoch {
+            let remaining = cap.0.saturating_sub(pool_claimed);
+            if amount > remaining {
+                return Err(format!(
+                    "Claim of {} exceeds the epoch limit for pool {}. Remaining allowance in epoch {}: {}",
+                    amount, pool_id, epoch, remaining
+                ));
+            }
+        }
+
+        if limits.max_per_user_per_epoch.is_some() {
+            self.user_epoch_claims.insert(
+                user_key,
+                EpochUsage {
+                    epoch,
+                    claimed: user_claimed + amount,
+                },
+            );
+        }
+        if limits.max_per_epoch.is_some() {
+            self.pool_epoch_claims.insert(
+                pool_id,
+                EpochUsage {
+                    epoch,
+                    claimed: pool_claimed + amount,
+                },
+            );
+        }
+        Ok(())
+    }
+}
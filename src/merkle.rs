@@ -0,0 +1,254 @@
+use near_sdk::json_types::{Base58CryptoHash, U128, U64};
+use near_sdk::{env, near, require, AccountId, CryptoHash};
+
+use crate::events::ContractEvent;
+use crate::history::ClaimSource;
+use crate::pause::PausableFeature;
+use crate::roles::Role;
+use crate::{assert_one_yocto, Contract, ContractExt};
+
+/// Longest proof accepted by `claim_with_proof`, enough for 2^32 leaves.
+const MAX_PROOF_LENGTH: usize = 32;
+
+/// Domain separators so a leaf can never be passed off as an inner node.
+const LEAF_PREFIX: u8 = 0x00;
+const NODE_PREFIX: u8 = 0x01;
+
+/// A published reward distribution for one pool epoch.
+#[near(serializers = [borsh, json])]
+#[derive(Clone)]
+pub struct ClaimRoot {
+    pub pool_id: u32,
+    pub epoch: u32,
+    pub root: Base58CryptoHash,
+    /// Sum of every leaf amount in the tree.
+    pub total: U128,
+    /// Amount claimed against this root so far.
+    pub claimed: U128,
+    pub set_by: AccountId,
+    pub set_at: U64,
+}
+
+/// Leaf hash for `account_id` receiving `amount` at position `index`:
+/// `sha256(0x00 || index as u32 LE || amount as u128 LE || account_id)`.
+pub fn claim_leaf_hash(index: u32, account_id: &AccountId, amount: u128) -> CryptoHash {
+    let mut data = Vec::with_capacity(1 + 4 + 16 + account_id.len());
+    data.push(LEAF_PREFIX);
+    data.extend_from_slice(&index.to_le_bytes());
+    data.extend_from_slice(&amount.to_le_bytes());
+    data.extend_from_slice(account_id.as_bytes());
+    env::sha256_array(&data)
+}
+
+/// Inner node hash over a sorted pair: `sha256(0x01 || min(a, b) || max(a, b))`.
+fn node_hash(a: &CryptoHash, b: &CryptoHash) -> CryptoHash {
+    let (left, right) = if a <= b { (a, b) } else { (b, a) };
+    let mut data = [0u8; 65];
+    data[0] = NODE_PREFIX;
+    data[1..33].copy_from_slice(left);
+    data[33..].copy_from_slice(right);
+    env::sha256_array(&data)
+}
+
+#[near]
+impl Contract {
+    /// Publish the Merkle root of a pool epoch's reward distribution.
+    /// `RewardDistributor` only. `total` is reserved out of the pool's current
+    /// amount, and roots above the multisig claim limit must go through
+    /// `propose_action`. A root can be replaced until the first claim against
+    /// it; the replaced root's reservation goes back to the pool first.
+    #[payable]
+    pub fn set_claim_root(
+        &mut self,
+        pool_id: u32,
+        epoch: u32,
+        root: Base58CryptoHash,
+        total: U128,
+    ) {
+        assert_one_yocto();
+        self.assert_role(Role::RewardDistributor);
+        self.multisig_assert_not_required_for_claim(total.0);
+        self.internal_set_claim_root(pool_id, epoch, root, total);
+    }
+
+    /// Claim the caller's reward from a published root. `index` and `amount`
+    /// are the caller's leaf, `proof` the sibling hashes from leaf to root.
+    /// Attach enough NEAR to register for storage if needed.
+    #[payable]
+    pub fn claim_with_proof(
+        &mut self,
+        pool_id: u32,
+        epoch: u32,
+        index: u32,
+        amount: U128,
+        proof: Vec<Base58CryptoHash>,
+    ) {
+        self.assert_not_paused(PausableFeature::ClaimRewards);
+        require!(amount.0 > 0, "Invalid amount to claim");
+        require!(
+            proof.len() <= MAX_PROOF_LENGTH,
+            format!("Proof must not exceed {} hashes", MAX_PROOF_LENGTH)
+        );
+        let account_id = env::predecessor_account_id();
+        require!(
+            account_id != env::current_account_id(),
+            "The contract account cannot claim rewards"
+        );
+
+        let claim_root = self.claim_roots.get(&(pool_id, epoch)).unwrap_or_else(|| {
+            env::panic_str(&format!(
+                "No claim root for pool {} epoch {}",
+                pool_id, epoch
+            ))
+        });
+        let computed = proof.iter().fold(
+            claim_leaf_hash(index, &account_id, amount.0),
+            |hash, sibling| node_hash(&hash, &(*sibling).into()),
+        );
+        require!(
+            computed == CryptoHash::from(claim_root.root),
+            "Invalid Merkle proof"
+        );
+        let claimed = claim_root
+            .claimed
+            .0
+            .checked_add(amount.0)
+            .filter(|claimed| *claimed <= claim_root.total.0)
+            .unwrap_or_else(|| env::panic_str("Claim exceeds the root total"));
+
+        self.internal_set_claimed(pool_id, epoch, index);
+        if let Some(claim_root) = self.claim_roots.get_mut(&(pool_id, epoch)) {
+            claim_root.claimed = U128(claimed);
+        }
+
+        self.internal_register_with_deposit(&account_id);
+        self.internal_consume_claim_allowance(pool_id, &account_id, amount.0);
+        self.internal_unlock_claim_root_escrow(amount.0);
+        self.internal_pay_from_escrow(
+            &account_id,
+            amount.0,
+            format!("Reward claim from pool_id: {} epoch: {}", pool_id, epoch),
+        );
+        self.internal_record_claim(&account_id, pool_id, amount.0, ClaimSource::MerkleProof);
+
+        ContractEvent::RewardClaimedWithProof {
+            pool_id,
+            epoch,
+            index,
+            account_id,
+            amount,
+        }
+        .emit();
+    }
+
+    /// Published root for a pool epoch, if any
+    pub fn get_claim_root(&self, pool_id: u32, epoch: u32) -> Option<ClaimRoot> {
+        self.claim_roots.get(&(pool_id, epoch)).cloned()
+    }
+
+    /// Whether the leaf at `index` has already been claimed
+    pub fn is_claimed(&self, pool_id: u32, epoch: u32, index: u32) -> bool {
+        let (word, bit) = (index / 64, index % 64);
+        self.claimed_bitmap
+            .get(&(pool_id, epoch, word))
+            .is_some_and(|bits| bits & (1 << bit) != 0)
+    }
+}
+
+impl Contract {
+    /// Store the root for a pool epoch once the caller has been authorized.
+    pub(crate) fn internal_set_claim_root(
+        &mut self,
+        pool_id: u32,
+        epoch: u32,
+        root: Base58CryptoHash,
+        total: U128,
+    ) {
+        require!(total.0 > 0, "Root total must be greater than zero");
+
+        let released = match self.claim_roots.get(&(pool_id, epoch)) {
+            Some(existing) => {
+                require!(
+                    existing.claimed.0 == 0,
+                    format!(
+                        "Claims have already been made against pool {} epoch {}",
+                        pool_id, epoch
+                    )
+                );
+                existing.total.0 - existing.claimed.0
+            }
+            None => 0,
+        };
+
+        // Release the replaced root's reservation, then reserve the new total
+        self.internal_refill_before_claim(pool_id);
+        let pool = self.internal_open_pool_mut(pool_id);
+        pool.amount = U128(
+            pool.amount
+                .0
+                .checked_add(released)
+                .expect("Pool amount overflow"),
+        );
+        self.internal_unlock_claim_root_escrow(released);
+        self.internal_withdraw_from_pool(pool_id, total.0);
+        self.internal_lock_claim_root_escrow(total.0);
+
+        let set_by = env::predecessor_account_id();
+        self.claim_roots.insert(
+            (pool_id, epoch),
+            ClaimRoot {
+                pool_id,
+                epoch,
+                root,
+                total,
+                claimed: U128(0),
+                set_by: set_by.clone(),
+                set_at: U64(env::block_timestamp()),
+            },
+        );
+
+        ContractEvent::ClaimRootSet {
+            pool_id,
+            epoch,
+            root,
+            total,
+            set_by,
+        }
+        .emit();
+    }
+
+    fn internal_lock_claim_root_escrow(&mut self, amount: u128) {
+        self.claim_root_escrow = U128(
+            self.claim_root_escrow
+                .0
+                .checked_add(amount)
+                .expect("Claim root escrow overflow"),
+        );
+    }
+
+    fn internal_unlock_claim_root_escrow(&mut self, amount: u128) {
+        self.claim_root_escrow = U128(
+            self.claim_root_escrow
+                .0
+                .checked_sub(amount)
+                .expect("Claim root escrow underflow"),
+        );
+    }
+
+    /// Mark the leaf at `index` as claimed, panicking if it already was.
+    fn internal_set_claimed(&mut self, pool_id: u32, epoch: u32, index: u32) {
+        let (word, bit) = (index / 64, index % 64);
+        let bits = self
+            .claimed_bitmap
+            .entry((pool_id, epoch, word))
+            .or_insert(0);
+        require!(
+            *bits & (1 << bit) == 0,
+            format!(
+                "Leaf {} of pool {} epoch {} has already been claimed",
+                index, pool_id, epoch
+            )
+        );
+        *bits |= 1 << bit;
+    }
+}
@@ -0,0 +1,394 @@
+use near_contract_standards::fungible_token::metadata::FungibleTokenMetadata;
+use near_contract_standards::fungible_token::FungibleToken;
+use near_sdk::collections::{LazyOption, LookupMap, LookupSet};
+use near_sdk::json_types::{U128, U64};
+use near_sdk::store::{self, IterableMap};
+use near_sdk::{env, log, near, require, AccountId};
+
+use crate::multisig::MultisigConfig;
+use crate::pools::{
+    PoolKind, RefillPolicy, RewardPool, GLOBAL_TAPPING_POOL_ID, LOOT_RAFFLE_POOL_ID,
+};
+use crate::staking::{StakingConfig, StakingState};
+use crate::timelock::DEFAULT_TIMELOCK_DELAY_NS;
+use crate::{
+    Contract, ContractExt, DecayFactor, EmissionConfig, EmissionsAccount, PendingOwnershipTransfer,
+    StorageKey, OWNERSHIP_TRANSFER_EXPIRY_NS, TOKEN_UNIT,
+};
+
+/// Emissions entry as stored per owner account before the schedule became
+/// contract-wide, with the decay factor as a float.
+#[near(serializers = [borsh])]
+struct EmissionsAccountV1 {
+    initial_emissions: U64,
+    decay_factor: f64,
+    current_month: u32,
+    current_emissions: U64,
+    last_mint_timestamp: U64,
+}
+
+#[near(serializers = [borsh])]
+struct RafflePoolV1 {
+    pool_id: u32,
+    amount: U128,
+    total_amount: U128,
+}
+
+#[near(serializers = [borsh])]
+struct TappingPoolV1 {
+    pool_id: u32,
+    amount: U128,
+}
+
+/// State layout of the originally deployed contract, with the emissions
+/// schedule stored per owner account and one map per legacy pool.
+#[near(serializers = [borsh])]
+struct ContractV1 {
+    token: FungibleToken,
+    metadata: LazyOption<FungibleTokenMetadata>,
+    emissions_account: LookupMap<AccountId, EmissionsAccountV1>,
+    loot_raffle_pool: LookupMap<u32, RafflePoolV1>,
+    global_tapping_pool: LookupMap<u32, TappingPoolV1>,
+    owner_id: AccountId,
+    proposed_owner: Option<AccountId>,
+}
+
+/// Scale the legacy float decay factor was stored with (10 decimal places).
+const LEGACY_DECAY_DENOMINATOR: u128 = 10_000_000_000;
+
+/// Exact ratio for the legacy float decay factor, rounded to 10 decimal places.
+fn decay_factor_from_f64(decay_factor: f64) -> DecayFactor {
+    require!(
+        (0.0..=1.0).contains(&decay_factor),
+        format!("Invalid legacy decay factor {}", decay_factor)
+    );
+    DecayFactor {
+        numerator: U128((decay_factor * LEGACY_DECAY_DENOMINATOR as f64).round() as u128),
+        denominator: U128(LEGACY_DECAY_DENOMINATOR),
+    }
+}
+
+impl EmissionsAccountV1 {
+    /// Base units minted for months 0 through `current_month - 1`, replaying
+    /// the float decay the legacy `mint` applied.
+    fn total_minted(&self) -> u128 {
+        let mut emissions = self.initial_emissions.0;
+        let mut total_minted: u128 = 0;
+        for month in 0..self.current_month {
+            if month > 0 {
+                emissions = (emissions as f64 * self.decay_factor) as u64;
+            }
+            total_minted = total_minted
+                .checked_add(u128::from(emissions) * TOKEN_UNIT)
+                .expect("Total minted addition overflow");
+        }
+        total_minted
+    }
+}
+
+#[near]
+impl Contract {
+    /// Upgrade the originally deployed state to the current layout.
+    ///
+    /// Earlier ownership transfers created a fresh emissions entry for each
+    /// new owner, so `previous_owner_ids` lists any past owners whose entries
+    /// should be considered too. The entry that has progressed furthest is
+    /// kept and all listed entries are removed from storage. The two legacy
+    /// pools move into the pool registry and their amounts are minted into
+    /// escrow, `max_supply` becomes the supply cap, and the owner receives
+    /// every role.
+    #[private]
+    #[init(ignore_state)]
+    pub fn migrate(previous_owner_ids: Option<Vec<AccountId>>, max_supply: U128) -> Self {
+        let mut old: ContractV1 = env::state_read().expect("Failed to read old state");
+
+        let mut candidates = vec![old.owner_id.clone()];
+        candidates.extend(previous_owner_ids.unwrap_or_default());
+
+        let mut legacy: Option<(AccountId, EmissionsAccountV1)> = None;
+        for account_id in candidates {
+            if let Some(entry) = old.emissions_account.remove(&account_id) {
+                let is_further = legacy
+                    .as_ref()
+                    .is_none_or(|(_, kept)| entry.current_month > kept.current_month);
+                if is_further {
+                    legacy = Some((account_id, entry));
+                }
+            }
+        }
+        let (source_account, legacy) = legacy.expect("No emissions account found to migrate");
+        log!(
+            "Migrated emissions schedule from {} at month {}",
+            source_account,
+            legacy.current_month
+        );
+
+        let emission_config = EmissionConfig {
+            initial_emissions: legacy.initial_emissions,
+            decay_factor: decay_factor_from_f64(legacy.decay_factor),
+            ..EmissionConfig::default()
+        };
+        let emissions = EmissionsAccount {
+            initial_emissions: legacy.initial_emissions,
+            current_month: legacy.current_month,
+            current_emissions: legacy.current_emissions,
+            total_minted: U128(legacy.total_minted()),
+            last_mint_timestamp: legacy.last_mint_timestamp,
+        };
+
+        let mut pools = IterableMap::new(StorageKey::RewardPools);
+        if let Some(raffle_pool) = old.loot_raffle_pool.remove(&LOOT_RAFFLE_POOL_ID) {
+            pools.insert(
+                LOOT_RAFFLE_POOL_ID,
+                RewardPool {
+                    pool_id: raffle_pool.pool_id,
+                    kind: PoolKind::LootRaffle,
+                    amount: raffle_pool.amount,
+                    total_amount: raffle_pool.total_amount,
+                    refill_policy: RefillPolicy::Decay,
+                    closed: false,
+                },
+            );
+        }
+        if let Some(tapping_pool) = old.global_tapping_pool.remove(&GLOBAL_TAPPING_POOL_ID) {
+            let mut pool = RewardPool::new(
+                tapping_pool.pool_id,
+                PoolKind::GlobalTapping,
+                tapping_pool.amount,
+                RefillPolicy::Reset {
+                    amount: emission_config.tapping_reset,
+                },
+            );
+            pool.total_amount = tapping_pool.amount;
+            pools.insert(GLOBAL_TAPPING_POOL_ID, pool);
+        }
+
+        let pending_ownership_transfer = old.proposed_owner.map(|proposed_owner| {
+            let proposed_at = env::block_timestamp();
+            PendingOwnershipTransfer {
+                proposed_owner,
+                proposed_at: U64(proposed_at),
+                expires_at: U64(proposed_at.saturating_add(OWNERSHIP_TRANSFER_EXPIRY_NS)),
+            }
+        });
+
+        let mut this = Self {
+            token: old.token,
+            metadata: old.metadata,
+            emissions,
+            pools,
+            next_pool_id: GLOBAL_TAPPING_POOL_ID + 1,
+            paused_features: LookupSet::new(StorageKey::PausedFeatures),
+            emission_config,
+            max_supply,
+            owner_id: old.owner_id,
+            pending_ownership_transfer,
+            timelock_delay: U64(DEFAULT_TIMELOCK_DELAY_NS),
+            queued_actions: IterableMap::new(StorageKey::QueuedActions),
+            next_action_id: 0,
+            multisig_config: MultisigConfig::default(),
+            multisig_proposals: IterableMap::new(StorageKey::MultisigProposals),
+            next_proposal_id: 0,
+            claim_roots: store::LookupMap::new(StorageKey::ClaimRoots),
+            claimed_bitmap: store::LookupMap::new(StorageKey::ClaimedBitmap),
+            claim_root_escrow: U128(0),
+            voucher_signer: None,
+            tap_signer: None,
+            used_voucher_nonces: LookupSet::new(StorageKey::UsedVoucherNonces),
+            user_reward_stats: store::LookupMap::new(StorageKey::UserRewardStats),
+            recent_claims: store::LookupMap::new(StorageKey::RecentClaims),
+            next_claim_seq: 0,
+            pool_claim_limits: store::LookupMap::new(StorageKey::PoolClaimLimits),
+            pool_epoch_claims: store::LookupMap::new(StorageKey::PoolEpochClaims),
+            user_epoch_claims: store::LookupMap::new(StorageKey::UserEpochClaims),
+            tapping_refill: None,
+            raffle_rounds: IterableMap::new(StorageKey::RaffleRounds),
+            next_raffle_round_id: 0,
+            raffle_entries: store::LookupMap::new(StorageKey::RaffleEntries),
+            raffle_tickets: store::LookupMap::new(StorageKey::RaffleTickets),
+            raffle_escrow: U128(0),
+            current_distribution_epochs: store::LookupMap::new(
+                StorageKey::CurrentDistributionEpochs,
+            ),
+            epoch_distributions: store::LookupMap::new(StorageKey::EpochDistributions),
+            epoch_weights: store::LookupMap::new(StorageKey::EpochWeights),
+            distribution_escrow: U128(0),
+            staking_config: StakingConfig::default(),
+            staking: StakingState::default(),
+            stakes: store::LookupMap::new(StorageKey::Stakes),
+            ownership_renounced: false,
+        };
+        require!(
+            this.token.total_supply <= this.max_supply.0,
+            "The current total supply exceeds max_supply"
+        );
+
+        let owner_id = this.owner_id.clone();
+        this.internal_grant_all_roles(&owner_id);
+
+        // Back the legacy pools with escrowed tokens. Their amounts were
+        // already minted to the owner, so they move out of the owner's balance
+        let escrow_id = env::current_account_id();
+        if !this.token.accounts.contains_key(&escrow_id) {
+            this.token.internal_register_account(&escrow_id);
+        }
+        let pool_amount = this.internal_total_pool_amount();
+        let owner_balance = this.token.accounts.get(&owner_id).unwrap_or(0);
+        require!(
+            owner_balance >= pool_amount,
+            format!(
+                "The owner {} holds {} but the legacy pools need {} to be escrowed",
+                owner_id, owner_balance, pool_amount
+            )
+        );
+        if pool_amount > 0 && owner_id != escrow_id {
+            this.token.internal_transfer(
+                &owner_id,
+                &escrow_id,
+                pool_amount,
+                Some("Legacy reward pool allocation".to_string()),
+            );
+        }
+
+        this
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_contract_standards::fungible_token::metadata::FT_METADATA_SPEC;
+    use near_contract_standards::fungible_token::FungibleTokenCore;
+    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::testing_env;
+
+    use super::*;
+    use crate::roles::Role;
+
+    const LEGACY_RAFFLE_AMOUNT: u128 = 40_000_000 * TOKEN_UNIT;
+    const LEGACY_TAPPING_AMOUNT: u128 = 1_000_000_000 * TOKEN_UNIT;
+
+    /// The owner's schedule after three monthly mints
+    fn legacy_emissions() -> EmissionsAccountV1 {
+        EmissionsAccountV1 {
+            initial_emissions: U64(3_000_000_000),
+            decay_factor: 0.8705505633,
+            current_month: 3,
+            current_emissions: U64(2_273_624_755),
+            last_mint_timestamp: U64(1_000),
+        }
+    }
+
+    /// Write the state the original contract left after three monthly mints,
+    /// with a pending ownership proposal. The owner has sent `spent` of the
+    /// minted tokens to `accounts(2)`.
+    fn write_baseline_state(owner_id: &AccountId, proposed_owner: &AccountId, spent: u128) -> u128 {
+        let mut token = FungibleToken::new(StorageKey::FungibleToken);
+        token.internal_register_account(owner_id);
+        token.internal_register_account(&accounts(2));
+        let legacy = legacy_emissions();
+        let minted = legacy.total_minted();
+        token.internal_deposit(owner_id, minted - spent);
+        token.internal_deposit(&accounts(2), spent);
+
+        let mut emissions_account = LookupMap::new(b"e");
+        emissions_account.insert(owner_id, &legacy);
+        let mut loot_raffle_pool = LookupMap::new(b"l");
+        loot_raffle_pool.insert(
+            &LOOT_RAFFLE_POOL_ID,
+            &RafflePoolV1 {
+                pool_id: LOOT_RAFFLE_POOL_ID,
+                amount: U128(LEGACY_RAFFLE_AMOUNT),
+                total_amount: U128(120_000_000 * TOKEN_UNIT),
+            },
+        );
+        let mut global_tapping_pool = LookupMap::new(b"g");
+        global_tapping_pool.insert(
+            &GLOBAL_TAPPING_POOL_ID,
+            &TappingPoolV1 {
+                pool_id: GLOBAL_TAPPING_POOL_ID,
+                amount: U128(LEGACY_TAPPING_AMOUNT),
+            },
+        );
+
+        env::state_write(&ContractV1 {
+            token,
+            metadata: LazyOption::new(
+                StorageKey::Metadata,
+                Some(&FungibleTokenMetadata {
+                    spec: FT_METADATA_SPEC.to_string(),
+                    name: "PUSH THE BUTTON PTB".to_string(),
+                    symbol: "PUSH".to_string(),
+                    icon: None,
+                    reference: None,
+                    reference_hash: None,
+                    decimals: 5,
+                }),
+            ),
+            emissions_account,
+            loot_raffle_pool,
+            global_tapping_pool,
+            owner_id: owner_id.clone(),
+            proposed_owner: Some(proposed_owner.clone()),
+        });
+        minted
+    }
+
+    fn setup_context() {
+        let context = VMContextBuilder::new()
+            .current_account_id(accounts(5))
+            .predecessor_account_id(accounts(5))
+            .block_timestamp(2_000)
+            .build();
+        testing_env!(context);
+    }
+
+    #[test]
+    fn migrate_from_baseline_state() {
+        setup_context();
+        let owner_id = accounts(0);
+        let minted = write_baseline_state(&owner_id, &accounts(1), 0);
+
+        let contract = Contract::migrate(None, U128(u128::MAX));
+
+        let config = contract.get_emission_config();
+        assert_eq!(config.decay_factor.numerator.0, 8_705_505_633);
+        assert_eq!(config.decay_factor.denominator.0, LEGACY_DECAY_DENOMINATOR);
+        let emissions = contract.get_emissions_account();
+        assert_eq!(emissions.current_month, 3);
+        assert_eq!(emissions.current_emissions.0, 2_273_624_755);
+        assert_eq!(emissions.total_minted.0, minted);
+        assert_eq!(emissions.last_mint_timestamp.0, 1_000);
+
+        let raffle_pool = contract.get_pool(LOOT_RAFFLE_POOL_ID).unwrap();
+        assert_eq!(raffle_pool.amount.0, LEGACY_RAFFLE_AMOUNT);
+        assert_eq!(raffle_pool.total_amount.0, 120_000_000 * TOKEN_UNIT);
+        let tapping_pool = contract.get_pool(GLOBAL_TAPPING_POOL_ID).unwrap();
+        assert_eq!(tapping_pool.amount.0, LEGACY_TAPPING_AMOUNT);
+
+        let pool_amount = LEGACY_RAFFLE_AMOUNT + LEGACY_TAPPING_AMOUNT;
+        assert_eq!(contract.ft_balance_of(accounts(5)).0, pool_amount);
+        assert_eq!(
+            contract.ft_balance_of(owner_id.clone()).0,
+            minted - pool_amount
+        );
+        assert_eq!(contract.ft_total_supply().0, minted);
+        assert!(contract.get_escrow_status().is_solvent);
+
+        let pending = contract.get_pending_ownership_transfer().unwrap();
+        assert_eq!(pending.proposed_owner, accounts(1));
+        assert_eq!(pending.expires_at.0, 2_000 + OWNERSHIP_TRANSFER_EXPIRY_NS);
+        for role in Role::ALL {
+            assert!(Contract::account_has_role(&owner_id, role));
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "but the legacy pools need")]
+    fn migrate_requires_owner_to_cover_legacy_pools() {
+        setup_context();
+        let spent = legacy_emissions().total_minted() - LEGACY_RAFFLE_AMOUNT;
+        write_baseline_state(&accounts(0), &accounts(1), spent);
+
+        Contract::migrate(None, U128(u128::MAX));
+    }
+}
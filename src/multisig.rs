@@ -0,0 +1,382 @@
+use near_sdk::json_types::{Base58CryptoHash, U128, U64};
+use near_sdk::{env, near, require, AccountId};
+
+use crate::events::ContractEvent;
+use crate::raffle::prize_total;
+use crate::roles::Role;
+use crate::timelock::TimelockAction;
+use crate::{assert_one_yocto, Contract, ContractExt};
+
+/// How long a proposal can collect approvals: 7 days, in nanoseconds.
+const MULTISIG_PROPOSAL_EXPIRY_NS: u64 = 7 * 24 * 60 * 60 * 1_000_000_000;
+
+/// Most signers a multisig config may list.
+const MAX_MULTISIG_SIGNERS: usize = 20;
+
+/// Most proposals returned by a single `get_multisig_proposals` call.
+const MAX_PROPOSALS_PER_PAGE: u32 = 100;
+
+/// M-of-N approval settings. A threshold of 0 leaves that action on its
+/// regular single-role path.
+#[near(serializers = [borsh, json])]
+#[derive(Clone, Default)]
+pub struct MultisigConfig {
+    pub signers: Vec<AccountId>,
+    /// Approvals needed before `mint` can run.
+    pub mint_threshold: u8,
+    /// Approvals needed before a claim above `large_claim_amount` can run.
+    pub large_claim_threshold: u8,
+    /// Claims strictly above this amount (base units) count as large.
+    pub large_claim_amount: U128,
+}
+
+impl MultisigConfig {
+    pub(crate) fn assert_valid(&self) {
+        require!(
+            self.signers.len() <= MAX_MULTISIG_SIGNERS,
+            format!("At most {} signers are allowed", MAX_MULTISIG_SIGNERS)
+        );
+        for (i, signer) in self.signers.iter().enumerate() {
+            require!(
+                !self.signers[..i].contains(signer),
+                format!("Duplicate signer {}", signer)
+            );
+        }
+        require!(
+            usize::from(self.mint_threshold) <= self.signers.len()
+                && usize::from(self.large_claim_threshold) <= self.signers.len(),
+            "Thresholds must not exceed the number of signers"
+        );
+    }
+}
+
+/// Operations that can be gated behind multisig approval.
+#[near(serializers = [borsh, json])]
+#[derive(Clone)]
+pub enum MultisigAction {
+    Mint {
+        max_epochs: Option<u32>,
+    },
+    ClaimRewards {
+        pool_id: u32,
+        amount: U128,
+        user_account: AccountId,
+    },
+    /// Publish a claim root whose total is above the large-claim limit.
+    SetClaimRoot {
+        pool_id: u32,
+        epoch: u32,
+        root: Base58CryptoHash,
+        total: U128,
+    },
+    /// Open a raffle round whose prize total is above the large-claim limit.
+    CreateRaffleRound {
+        ticket_price: U128,
+        prizes: Vec<U128>,
+        close_height: U64,
+    },
+    /// Finalize a distribution epoch whose reward is above the large-claim
+    /// limit. Always needs the large-claim threshold, since a refill applied
+    /// at execution can raise the reward.
+    FinalizeEpoch {
+        pool_id: u32,
+    },
+}
+
+#[near(serializers = [borsh, json])]
+#[derive(Clone)]
+pub struct MultisigProposal {
+    pub proposal_id: u64,
+    pub action: MultisigAction,
+    pub proposed_by: AccountId,
+    pub approvals: Vec<AccountId>,
+    pub proposed_at: U64,
+    /// Time (nanoseconds) after which the proposal can no longer be approved or executed.
+    pub expires_at: U64,
+}
+
+#[near]
+impl Contract {
+    /// Queue a change to the multisig signers and thresholds. Owner only.
+    /// Returns the queued action id.
+    #[payable]
+    pub fn set_multisig_config(&mut self, config: MultisigConfig) -> u64 {
+        assert_one_yocto();
+        self.assert_role(Role::Owner);
+        config.assert_valid();
+
+        self.internal_queue_action(TimelockAction::SetMultisigConfig { config })
+    }
+
+    /// Open a proposal for `action`. Signers only; the proposer's approval is
+    /// counted straight away. Returns the proposal id.
+    #[payable]
+    pub fn propose_action(&mut self, action: MultisigAction) -> u64 {
+        assert_one_yocto();
+        let proposer = self.assert_multisig_signer();
+        match &action {
+            MultisigAction::Mint { .. } => self.assert_role(Role::Minter),
+            MultisigAction::ClaimRewards { amount, .. } => {
+                require!(amount.0 > 0, "Claim amount must be greater than zero");
+                self.assert_role(Role::RewardDistributor)
+            }
+            MultisigAction::SetClaimRoot { total, .. } => {
+                require!(total.0 > 0, "Root total must be greater than zero");
+                self.assert_role(Role::RewardDistributor)
+            }
+            MultisigAction::CreateRaffleRound { .. } => self.assert_role(Role::RewardDistributor),
+            MultisigAction::FinalizeEpoch { .. } => self.assert_epoch_finalizer(),
+        }
+
+        let proposal_id = self.next_proposal_id;
+        self.next_proposal_id = proposal_id.checked_add(1).expect("Proposal id overflow");
+
+        let proposed_at = env::block_timestamp();
+        let expires_at = proposed_at
+            .checked_add(MULTISIG_PROPOSAL_EXPIRY_NS)
+            .expect("Expiry overflow");
+        let proposal = MultisigProposal {
+            proposal_id,
+            action,
+            proposed_by: proposer.clone(),
+            approvals: vec![proposer.clone()],
+            proposed_at: U64(proposed_at),
+            expires_at: U64(expires_at),
+        };
+        self.multisig_proposals
+            .insert(proposal_id, proposal.clone());
+
+        ContractEvent::MultisigActionProposed {
+            proposal_id,
+            action: proposal.action,
+            proposed_by: proposer,
+            expires_at: proposal.expires_at,
+        }
+        .emit();
+        proposal_id
+    }
+
+    /// Add the caller's approval to a pending proposal. Signers only.
+    #[payable]
+    pub fn approve_action(&mut self, proposal_id: u64) {
+        assert_one_yocto();
+        let signer = self.assert_multisig_signer();
+        let proposal = self
+            .multisig_proposals
+            .get_mut(&proposal_id)
+            .unwrap_or_else(|| env::panic_str(&format!("Proposal {} not found", proposal_id)));
+        require!(
+            env::block_timestamp() <= proposal.expires_at.0,
+            format!("Proposal {} has expired", proposal_id)
+        );
+        require!(
+            !proposal.approvals.contains(&signer),
+            format!("{} already approved proposal {}", signer, proposal_id)
+        );
+        proposal.approvals.push(signer.clone());
+
+        ContractEvent::MultisigActionApproved {
+            proposal_id,
+            approved_by: signer,
+        }
+        .emit();
+    }
+
+    /// Run a proposal once enough current signers have approved it. Signers
+    /// only. Attach enough NEAR to register the claim recipient if needed.
+    #[payable]
+    pub fn execute_action(&mut self, proposal_id: u64) {
+        let executor = self.assert_multisig_signer();
+        let proposal = self
+            .multisig_proposals
+            .remove(&proposal_id)
+            .unwrap_or_else(|| env::panic_str(&format!("Proposal {} not found", proposal_id)));
+        require!(
+            env::block_timestamp() <= proposal.expires_at.0,
+            format!("Proposal {} has expired", proposal_id)
+        );
+
+        let threshold = self.multisig_threshold_for(&proposal.action);
+        let approvals = self.multisig_valid_approvals(&proposal) as u8;
+        require!(
+            approvals >= threshold.max(1),
+            format!(
+                "Proposal {} has {} of {} required approvals",
+                proposal_id,
+                approvals,
+                threshold.max(1)
+            )
+        );
+
+        match proposal.action {
+            MultisigAction::Mint { max_epochs } => {
+                self.assert_role(Role::Minter);
+                self.internal_mint_epochs(max_epochs)
+            }
+            MultisigAction::ClaimRewards {
+                pool_id,
+                amount,
+                user_account,
+            } => {
+                self.assert_role(Role::RewardDistributor);
+                self.internal_claim_rewards(amount, pool_id, user_account)
+            }
+            MultisigAction::SetClaimRoot {
+                pool_id,
+                epoch,
+                root,
+                total,
+            } => {
+                self.assert_role(Role::RewardDistributor);
+                self.internal_set_claim_root(pool_id, epoch, root, total)
+            }
+            MultisigAction::CreateRaffleRound {
+                ticket_price,
+                prizes,
+                close_height,
+            } => {
+                self.assert_role(Role::RewardDistributor);
+                self.internal_create_raffle_round(ticket_price, prizes, close_height);
+            }
+            MultisigAction::FinalizeEpoch { pool_id } => {
+                self.assert_epoch_finalizer();
+                self.internal_finalize_epoch(pool_id);
+            }
+        }
+
+        ContractEvent::MultisigActionExecuted {
+            proposal_id,
+            executed_by: executor,
+        }
+        .emit();
+    }
+
+    /// Withdraw a pending proposal. The proposer or a `Pauser` (guardian) may cancel.
+    #[payable]
+    pub fn cancel_action(&mut self, proposal_id: u64) {
+        assert_one_yocto();
+        let caller = env::predecessor_account_id();
+        let proposal = self
+            .multisig_proposals
+            .remove(&proposal_id)
+            .unwrap_or_else(|| env::panic_str(&format!("Proposal {} not found", proposal_id)));
+        require!(
+            caller == proposal.proposed_by || Self::account_has_role(&caller, Role::Pauser),
+            "Only the proposer or a Pauser can cancel a proposal"
+        );
+
+        ContractEvent::MultisigActionCancelled {
+            proposal_id,
+            cancelled_by: caller,
+        }
+        .emit();
+    }
+
+    /// Current signers and thresholds
+    pub fn get_multisig_config(&self) -> MultisigConfig {
+        self.multisig_config.clone()
+    }
+
+    /// Paginated list of pending proposals
+    pub fn get_multisig_proposals(
+        &self,
+        from_index: Option<u32>,
+        limit: Option<u32>,
+    ) -> Vec<MultisigProposal> {
+        let limit = limit
+            .unwrap_or(MAX_PROPOSALS_PER_PAGE)
+            .min(MAX_PROPOSALS_PER_PAGE);
+        self.multisig_proposals
+            .values()
+            .skip(from_index.unwrap_or(0) as usize)
+            .take(limit as usize)
+            .cloned()
+            .collect()
+    }
+
+    /// A single pending proposal, if it exists
+    pub fn get_multisig_proposal(&self, proposal_id: u64) -> Option<MultisigProposal> {
+        self.multisig_proposals.get(&proposal_id).cloned()
+    }
+
+    /// Signers who have approved a pending proposal
+    pub fn get_proposal_approvals(&self, proposal_id: u64) -> Vec<AccountId> {
+        self.multisig_proposals
+            .get(&proposal_id)
+            .map(|proposal| proposal.approvals.clone())
+            .unwrap_or_default()
+    }
+}
+
+impl Contract {
+    pub(crate) fn internal_set_multisig_config(&mut self, config: MultisigConfig) {
+        config.assert_valid();
+        self.multisig_config = config;
+    }
+
+    /// Panic unless `mint` may run without multisig approval.
+    pub(crate) fn multisig_assert_not_required_for_mint(&self) {
+        require!(
+            self.multisig_config.mint_threshold == 0,
+            "Mint requires multisig approval; use propose_action"
+        );
+    }
+
+    /// Whether a claim of `amount` has to go through a multisig proposal.
+    pub(crate) fn multisig_claim_requires_approval(&self, amount: u128) -> bool {
+        self.multisig_config.large_claim_threshold > 0
+            && amount > self.multisig_config.large_claim_amount.0
+    }
+
+    /// Panic unless a claim of `amount` may run without multisig approval.
+    pub(crate) fn multisig_assert_not_required_for_claim(&self, amount: u128) {
+        require!(
+            !self.multisig_claim_requires_approval(amount),
+            format!(
+                "Claims above {} require multisig approval; use propose_action",
+                self.multisig_config.large_claim_amount.0
+            )
+        );
+    }
+
+    fn multisig_threshold_for(&self, action: &MultisigAction) -> u8 {
+        match action {
+            MultisigAction::Mint { .. } => self.multisig_config.mint_threshold,
+            MultisigAction::ClaimRewards { amount, .. }
+            | MultisigAction::SetClaimRoot { total: amount, .. } => {
+                self.multisig_large_claim_threshold(amount.0)
+            }
+            MultisigAction::CreateRaffleRound { prizes, .. } => {
+                self.multisig_large_claim_threshold(prize_total(prizes))
+            }
+            MultisigAction::FinalizeEpoch { .. } => self.multisig_config.large_claim_threshold,
+        }
+    }
+
+    fn multisig_large_claim_threshold(&self, amount: u128) -> u8 {
+        if amount > self.multisig_config.large_claim_amount.0 {
+            self.multisig_config.large_claim_threshold
+        } else {
+            0
+        }
+    }
+
+    /// Approvals from accounts that are still signers. Approvals from
+    /// removed signers stop counting.
+    fn multisig_valid_approvals(&self, proposal: &MultisigProposal) -> usize {
+        proposal
+            .approvals
+            .iter()
+            .filter(|approver| self.multisig_config.signers.contains(approver))
+            .count()
+    }
+
+    fn assert_multisig_signer(&self) -> AccountId {
+        let caller = env::predecessor_account_id();
+        require!(
+            self.multisig_config.signers.contains(&caller),
+            format!("Caller {} is not a multisig signer", caller)
+        );
+        caller
+    }
+}
@@ -0,0 +1,94 @@
+use near_sdk::{env, near, require, BorshStorageKey};
+
+use crate::events::ContractEvent;
+use crate::roles::Role;
+use crate::{assert_one_yocto, Contract, ContractExt};
+
+/// Contract features that can be halted independently.
+#[derive(BorshStorageKey, Clone, Copy, PartialEq, Debug)]
+#[near(serializers = [borsh, json])]
+pub enum PausableFeature {
+    FtTransfer,
+    FtTransferCall,
+    ClaimRewards,
+    Mint,
+    Burn,
+    /// Entering and drawing raffle rounds.
+    Raffle,
+    /// Staking, unstaking and claiming staking rewards.
+    Staking,
+    /// Submitting tap counts for the tapping pool.
+    Taps,
+}
+
+impl PausableFeature {
+    pub const ALL: [PausableFeature; 8] = [
+        PausableFeature::FtTransfer,
+        PausableFeature::FtTransferCall,
+        PausableFeature::ClaimRewards,
+        PausableFeature::Mint,
+        PausableFeature::Burn,
+        PausableFeature::Raffle,
+        PausableFeature::Staking,
+        PausableFeature::Taps,
+    ];
+}
+
+#[near]
+impl Contract {
+    /// Halt `feature`. Pauser (guardian) only.
+    #[payable]
+    pub fn pause(&mut self, feature: PausableFeature) {
+        assert_one_yocto();
+        self.assert_role(Role::Pauser);
+        require!(
+            self.paused_features.insert(&feature),
+            format!("{:?} is already paused", feature)
+        );
+
+        ContractEvent::FeaturePaused {
+            feature,
+            paused_by: env::predecessor_account_id(),
+        }
+        .emit();
+    }
+
+    /// Resume `feature`. Pauser (guardian) only.
+    #[payable]
+    pub fn unpause(&mut self, feature: PausableFeature) {
+        assert_one_yocto();
+        self.assert_role(Role::Pauser);
+        require!(
+            self.paused_features.remove(&feature),
+            format!("{:?} is not paused", feature)
+        );
+
+        ContractEvent::FeatureUnpaused {
+            feature,
+            unpaused_by: env::predecessor_account_id(),
+        }
+        .emit();
+    }
+
+    /// Whether `feature` is currently paused
+    pub fn is_paused(&self, feature: PausableFeature) -> bool {
+        self.paused_features.contains(&feature)
+    }
+
+    /// Every feature that is currently paused
+    pub fn get_paused_features(&self) -> Vec<PausableFeature> {
+        PausableFeature::ALL
+            .into_iter()
+            .filter(|feature| self.paused_features.contains(feature))
+            .collect()
+    }
+}
+
+impl Contract {
+    pub(crate) fn assert_not_paused(&self, feature: PausableFeature) {
+        require!(
+            !self.paused_features.contains(&feature),
+            format!("{:?} is paused", feature)
+        );
+    }
+}
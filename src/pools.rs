@@ -0,0 +1,470 @@
+use near_contract_standards::fungible_token::events::{FtBurn, FtMint, FtTransfer};
+use near_contract_standards::fungible_token::FungibleTokenCore;
+use near_sdk::json_types::U128;
+use near_sdk::{env, near, require, AccountId};
+
+use crate::events::ContractEvent;
+use crate::roles::Role;
+use crate::timelock::TimelockAction;
+use crate::{assert_one_yocto, Contract, ContractExt, DecayFactor};
+
+/// Pool id of the loot raffle pool created at initialization.
+pub const LOOT_RAFFLE_POOL_ID: u32 = 1;
+/// Pool id of the global tapping pool created at initialization.
+pub const GLOBAL_TAPPING_POOL_ID: u32 = 2;
+
+/// Most pools returned by a single `list_pools` call.
+const MAX_POOLS_PER_PAGE: u32 = 100;
+
+#[near(serializers = [borsh, json])]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PoolKind {
+    LootRaffle,
+    GlobalTapping,
+    Seasonal,
+}
+
+/// How a pool is topped up on every emission epoch minted by `mint`.
+#[near(serializers = [borsh, json])]
+#[derive(Clone, PartialEq, Debug)]
+pub enum RefillPolicy {
+    /// Set the pool amount back to `amount`.
+    Reset { amount: U128 },
+    /// Multiply the pool amount by the emission decay factor (skipped for month 0).
+    Decay,
+    /// Leave the pool amount untouched; the pool only grows through `fund_pool`.
+    None,
+}
+
+/// Pool balances compared against the tokens escrowed for them
+#[near(serializers = [json])]
+pub struct EscrowStatus {
+    pub escrow_account_id: AccountId,
+    pub escrow_balance: U128,
+    pub total_pool_amount: U128,
+    /// Claim root totals reserved out of their pools and not yet claimed.
+    pub claim_root_escrow_amount: U128,
+    /// Raffle prizes and ticket payments held for rounds not yet drawn.
+    pub raffle_escrow_amount: U128,
+    /// Rewards locked for finalized distribution epochs and not yet claimed.
+    pub distribution_escrow_amount: U128,
+    /// Staked tokens, tokens in unstake cooldown and unclaimed staking rewards.
+    pub staking_locked_amount: U128,
+    /// Emission set aside for scheduled tapping pool refills.
+    pub tapping_reserve_amount: U128,
+    /// `total_pool_amount + claim_root_escrow_amount + raffle_escrow_amount
+    /// + distribution_escrow_amount + staking_locked_amount
+    /// + tapping_reserve_amount <= escrow_balance`
+    pub is_solvent: bool,
+}
+
+#[near(serializers = [borsh, json])]
+#[derive(Clone)]
+pub struct RewardPool {
+    pub pool_id: u32,
+    pub kind: PoolKind,
+    pub amount: U128,
+    /// Running sum of the pool amount after each refill.
+    pub total_amount: U128,
+    pub refill_policy: RefillPolicy,
+    pub closed: bool,
+}
+
+impl RewardPool {
+    pub fn new(pool_id: u32, kind: PoolKind, amount: U128, refill_policy: RefillPolicy) -> Self {
+        Self {
+            pool_id,
+            kind,
+            amount,
+            total_amount: U128(0),
+            refill_policy,
+            closed: false,
+        }
+    }
+
+    /// Apply the refill policy for one emission epoch. Top-ups are taken
+    /// from `budget`, and stop short of the target once it runs out; whatever
+    /// the pool gives up is added to it.
+    pub fn apply_refill(
+        &mut self,
+        decay_factor: &DecayFactor,
+        decay_applies: bool,
+        budget: &mut u128,
+    ) {
+        let target = match &self.refill_policy {
+            RefillPolicy::Reset { amount } => amount.0,
+            RefillPolicy::Decay if decay_applies => decay_factor.apply(self.amount.0),
+            RefillPolicy::Decay | RefillPolicy::None => self.amount.0,
+        };
+        if target > self.amount.0 {
+            let top_up = (target - self.amount.0).min(*budget);
+            *budget -= top_up;
+            self.amount = U128(self.amount.0 + top_up);
+        } else {
+            *budget = budget
+                .checked_add(self.amount.0 - target)
+                .expect("Refill budget overflow");
+            self.amount = U128(target);
+        }
+        self.total_amount = U128(
+            self.total_amount
+                .0
+                .checked_add(self.amount.0)
+                .expect("Total amount addition overflow"),
+        );
+    }
+}
+
+#[near]
+impl Contract {
+    /// Queue creating a new reward pool behind the timelock. The new pool id
+    /// is announced in the `pool_created` event on execution. Returns the
+    /// queued action id.
+    #[payable]
+    pub fn create_pool(&mut self, kind: PoolKind, refill_policy: RefillPolicy) -> u64 {
+        assert_one_yocto();
+        self.assert_role(Role::ConfigAdmin);
+
+        self.internal_queue_action(TimelockAction::CreatePool {
+            kind,
+            refill_policy,
+        })
+    }
+
+    /// Move `amount` of the caller's tokens into escrow and add it to a pool
+    #[payable]
+    pub fn fund_pool(&mut self, pool_id: u32, amount: U128) {
+        assert_one_yocto();
+        self.assert_role(Role::ConfigAdmin);
+        require!(amount.0 > 0, "Invalid amount to fund");
+
+        let funder_id = env::predecessor_account_id();
+        let escrow_id = env::current_account_id();
+        if funder_id == escrow_id {
+            self.internal_assert_escrow_unlocked(&funder_id, amount.0);
+        } else {
+            self.token.internal_transfer(
+                &funder_id,
+                &escrow_id,
+                amount.0,
+                Some(format!("Funding pool_id: {}", pool_id)),
+            );
+        }
+
+        let pool = self.internal_open_pool_mut(pool_id);
+        pool.amount = U128(
+            pool.amount
+                .0
+                .checked_add(amount.0)
+                .expect("Pool amount addition overflow"),
+        );
+
+        ContractEvent::PoolFunded {
+            pool_id,
+            funded_by: env::predecessor_account_id(),
+            amount,
+        }
+        .emit();
+    }
+
+    /// Queue changing how a pool is refilled on future mints. Returns the
+    /// queued action id.
+    #[payable]
+    pub fn set_pool_refill_policy(&mut self, pool_id: u32, refill_policy: RefillPolicy) -> u64 {
+        assert_one_yocto();
+        self.assert_role(Role::ConfigAdmin);
+        self.internal_open_pool_mut(pool_id);
+
+        self.internal_queue_action(TimelockAction::SetPoolRefillPolicy {
+            pool_id,
+            refill_policy,
+        })
+    }
+
+    /// Queue closing a pool. Closed pools are kept for history but can no
+    /// longer be claimed from, funded or refilled; on execution their
+    /// remaining amount is released from escrow to the owner. Returns the
+    /// queued action id.
+    #[payable]
+    pub fn close_pool(&mut self, pool_id: u32) -> u64 {
+        assert_one_yocto();
+        self.assert_role(Role::ConfigAdmin);
+        self.internal_open_pool_mut(pool_id);
+
+        self.internal_queue_action(TimelockAction::ClosePool { pool_id })
+    }
+
+    /// A single reward pool by id
+    pub fn get_pool(&self, pool_id: u32) -> Option<RewardPool> {
+        self.pools.get(&pool_id).cloned()
+    }
+
+    /// Paginated list of reward pools, in creation order
+    pub fn list_pools(&self, from_index: Option<u32>, limit: Option<u32>) -> Vec<RewardPool> {
+        let limit = limit.unwrap_or(MAX_POOLS_PER_PAGE).min(MAX_POOLS_PER_PAGE);
+        self.pools
+            .values()
+            .skip(from_index.unwrap_or(0) as usize)
+            .take(limit as usize)
+            .cloned()
+            .collect()
+    }
+
+    /// Current state of the loot raffle pool
+    pub fn get_raffle_pool(&self) -> RewardPool {
+        self.internal_pool(LOOT_RAFFLE_POOL_ID).clone()
+    }
+
+    /// Current state of the global tapping pool
+    pub fn get_tapping_pool(&self) -> RewardPool {
+        self.internal_pool(GLOBAL_TAPPING_POOL_ID).clone()
+    }
+
+    /// Proves the pools are backed: the sum of open pool amounts never exceeds
+    /// the tokens held by the contract account
+    pub fn get_escrow_status(&self) -> EscrowStatus {
+        let escrow_account_id = env::current_account_id();
+        let escrow_balance = self.token.ft_balance_of(escrow_account_id.clone()).0;
+        let total_pool_amount = self.internal_total_pool_amount();
+        EscrowStatus {
+            escrow_account_id,
+            escrow_balance: U128(escrow_balance),
+            total_pool_amount: U128(total_pool_amount),
+            claim_root_escrow_amount: self.claim_root_escrow,
+            raffle_escrow_amount: self.raffle_escrow,
+            distribution_escrow_amount: self.distribution_escrow,
+            staking_locked_amount: U128(self.internal_staking_locked_amount()),
+            tapping_reserve_amount: U128(self.internal_tapping_reserve_amount()),
+            is_solvent: self.internal_locked_escrow_amount() <= escrow_balance,
+        }
+    }
+}
+
+impl Contract {
+    pub(crate) fn internal_create_pool(&mut self, kind: PoolKind, refill_policy: RefillPolicy) {
+        let pool_id = self.next_pool_id;
+        self.next_pool_id = pool_id.checked_add(1).expect("Pool id overflow");
+        self.pools.insert(
+            pool_id,
+            RewardPool::new(pool_id, kind, U128(0), refill_policy.clone()),
+        );
+
+        ContractEvent::PoolCreated {
+            pool_id,
+            kind,
+            refill_policy,
+        }
+        .emit();
+    }
+
+    pub(crate) fn internal_set_pool_refill_policy(
+        &mut self,
+        pool_id: u32,
+        refill_policy: RefillPolicy,
+    ) {
+        let pool = self.internal_open_pool_mut(pool_id);
+        pool.refill_policy = refill_policy.clone();
+
+        ContractEvent::PoolRefillPolicyUpdated {
+            pool_id,
+            refill_policy,
+        }
+        .emit();
+    }
+
+    pub(crate) fn internal_close_pool(&mut self, pool_id: u32) {
+        let pool = self.internal_open_pool_mut(pool_id);
+        let remaining_amount = pool.amount;
+        pool.amount = U128(0);
+        pool.closed = true;
+
+        let owner_id = self.owner_id.clone();
+        if remaining_amount.0 > 0 && owner_id != env::current_account_id() {
+            self.internal_pay_from_escrow(
+                &owner_id,
+                remaining_amount.0,
+                format!("Closing pool_id: {}", pool_id),
+            );
+        }
+
+        ContractEvent::PoolClosed {
+            pool_id,
+            remaining_amount,
+        }
+        .emit();
+    }
+
+    pub(crate) fn internal_pool(&self, pool_id: u32) -> &RewardPool {
+        self.pools
+            .get(&pool_id)
+            .unwrap_or_else(|| env::panic_str(&format!("Pool {} not found", pool_id)))
+    }
+
+    pub(crate) fn internal_open_pool_mut(&mut self, pool_id: u32) -> &mut RewardPool {
+        let pool = self
+            .pools
+            .get_mut(&pool_id)
+            .unwrap_or_else(|| env::panic_str(&format!("Pool {} not found", pool_id)));
+        require!(!pool.closed, format!("Pool {} is closed", pool_id));
+        pool
+    }
+
+    /// Apply every open pool's refill policy for one emission epoch, funding
+    /// top-ups from that epoch's `budget`, and return the total
+    /// `(added, removed)` across pools, in base units. A tapping pool on its
+    /// own schedule is left alone; its refill reserve is topped up instead.
+    pub(crate) fn internal_refill_pools(
+        &mut self,
+        decay_applies: bool,
+        budget: u128,
+    ) -> (u128, u128) {
+        let decay_factor = self.emission_config.decay_factor;
+        let tapping_on_schedule = self.tapping_refill.is_some();
+        let (mut added, mut removed) = (0u128, 0u128);
+        let mut budget = budget;
+        for pool in self
+            .pools
+            .values_mut()
+            .filter(|pool| !pool.closed)
+            .filter(|pool| !tapping_on_schedule || pool.pool_id != GLOBAL_TAPPING_POOL_ID)
+        {
+            let before = pool.amount.0;
+            pool.apply_refill(&decay_factor, decay_applies, &mut budget);
+            let after = pool.amount.0;
+            if after > before {
+                added = added
+                    .checked_add(after - before)
+                    .expect("Refill amount overflow");
+            } else {
+                removed += before - after;
+            }
+        }
+        added = added
+            .checked_add(self.internal_fund_tapping_reserve(&mut budget))
+            .expect("Refill amount overflow");
+        (added, removed)
+    }
+
+    /// Sum of the amounts held by all open pools.
+    pub(crate) fn internal_total_pool_amount(&self) -> u128 {
+        self.pools
+            .values()
+            .filter(|pool| !pool.closed)
+            .map(|pool| pool.amount.0)
+            .fold(0u128, |total, amount| {
+                total.checked_add(amount).expect("Pool total overflow")
+            })
+    }
+
+    /// Tokens in the contract account that belong to pools, claim roots,
+    /// raffle rounds, finalized distribution epochs, stakers or the tapping
+    /// refill reserve.
+    pub(crate) fn internal_locked_escrow_amount(&self) -> u128 {
+        self.internal_total_pool_amount()
+            .checked_add(self.claim_root_escrow.0)
+            .and_then(|total| total.checked_add(self.raffle_escrow.0))
+            .and_then(|total| total.checked_add(self.distribution_escrow.0))
+            .and_then(|total| total.checked_add(self.internal_staking_locked_amount()))
+            .and_then(|total| total.checked_add(self.internal_tapping_reserve_amount()))
+            .expect("Escrow total overflow")
+    }
+
+    /// Mint `amount` into the contract account to back pool allocations.
+    pub(crate) fn internal_mint_to_escrow(&mut self, amount: u128, memo: &str) {
+        if amount == 0 {
+            return;
+        }
+        let escrow_id = env::current_account_id();
+        self.internal_mint(&escrow_id, amount);
+        FtMint {
+            owner_id: &escrow_id,
+            amount: U128(amount),
+            memo: Some(memo),
+        }
+        .emit();
+    }
+
+    /// Burn `amount` from the contract account after pool allocations shrink.
+    pub(crate) fn internal_burn_from_escrow(&mut self, amount: u128, memo: &str) {
+        if amount == 0 {
+            return;
+        }
+        let escrow_id = env::current_account_id();
+        self.token.internal_withdraw(&escrow_id, amount);
+        FtBurn {
+            owner_id: &escrow_id,
+            amount: U128(amount),
+            memo: Some(memo),
+        }
+        .emit();
+    }
+
+    /// Transfer `amount` out of escrow. The caller must already have deducted
+    /// it from the pool it belongs to.
+    pub(crate) fn internal_pay_from_escrow(
+        &mut self,
+        receiver_id: &AccountId,
+        amount: u128,
+        memo: String,
+    ) {
+        self.token
+            .internal_transfer(&env::current_account_id(), receiver_id, amount, Some(memo));
+    }
+
+    /// Transfer several payouts out of escrow and log them as a single
+    /// `ft_transfer` event. The caller must already have deducted the total
+    /// from the pool it belongs to and checked every receiver is registered.
+    pub(crate) fn internal_pay_many_from_escrow(
+        &mut self,
+        payouts: &[(AccountId, u128)],
+        memo: &str,
+    ) {
+        let escrow_id = env::current_account_id();
+        let mut transfers = Vec::with_capacity(payouts.len());
+        for (receiver_id, amount) in payouts {
+            self.token.internal_withdraw(&escrow_id, *amount);
+            self.token.internal_deposit(receiver_id, *amount);
+            transfers.push(FtTransfer {
+                old_owner_id: &escrow_id,
+                new_owner_id: receiver_id,
+                amount: U128(*amount),
+                memo: Some(memo),
+            });
+        }
+        if !transfers.is_empty() {
+            FtTransfer::emit_many(&transfers);
+        }
+    }
+
+    /// When the contract account itself spends tokens (e.g. it is also the
+    /// owner), make sure it cannot touch what is escrowed for the pools.
+    pub(crate) fn internal_assert_escrow_unlocked(&self, sender_id: &AccountId, amount: u128) {
+        if *sender_id != env::current_account_id() {
+            return;
+        }
+        let balance = self.token.ft_balance_of(sender_id.clone()).0;
+        let locked = self.internal_locked_escrow_amount();
+        require!(
+            balance.saturating_sub(locked) >= amount,
+            format!(
+                "Insufficient unlocked balance. Available: {}, Locked in escrow: {}, Required: {}",
+                balance.saturating_sub(locked),
+                locked,
+                amount
+            )
+        );
+    }
+
+    /// Deduct `amount` from an open pool, panicking if it holds too little.
+    pub(crate) fn internal_withdraw_from_pool(&mut self, pool_id: u32, amount: u128) {
+        let pool = self.internal_open_pool_mut(pool_id);
+        require!(
+            amount <= pool.amount.0,
+            format!(
+                "Insufficient funds in pool {}. Available: {}, Requested: {}",
+                pool_id, pool.amount.0, amount
+            )
+        );
+        pool.amount = U128(pool.amount.0 - amount);
+    }
+}
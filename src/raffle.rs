@@ -0,0 +1,670 @@
+use near_sdk::json_types::{Base58CryptoHash, Base64VecU8, U128, U64};
+use near_sdk::CryptoHash;
+use near_sdk::{env, near, require, AccountId, NearToken};
+
+use crate::events::ContractEvent;
+use crate::history::ClaimSource;
+use crate::pause::PausableFeature;
+use crate::pools::LOOT_RAFFLE_POOL_ID;
+use crate::roles::Role;
+use crate::{assert_one_yocto, Contract, ContractExt};
+
+/// Most prizes a single round can award.
+const MAX_PRIZES_PER_ROUND: usize = 20;
+
+/// Most rounds or entries returned by a single paginated view.
+const MAX_RAFFLE_ITEMS_PER_PAGE: u32 = 100;
+
+/// Longest reveal window after a round closes, about one day of blocks, so
+/// ticket payments cannot be held hostage by a far-off deadline.
+const MAX_REVEAL_WINDOW_BLOCKS: u64 = 86_400;
+
+#[near(serializers = [borsh, json])]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum RaffleStatus {
+    /// Accepting entries until `close_height`, then waiting to be drawn.
+    Open,
+    Drawn,
+    /// The committed secret was not revealed in time; entrants reclaim
+    /// their tickets and the prizes went back to the pool.
+    Refunded,
+}
+
+#[near(serializers = [borsh, json])]
+#[derive(Clone)]
+pub struct RaffleWinner {
+    pub account_id: AccountId,
+    /// Zero-based winning ticket number.
+    pub ticket: U64,
+    pub prize: U128,
+    /// `false` if the winner had unregistered from storage by the draw; the
+    /// prize went back to the loot raffle pool instead.
+    pub paid: bool,
+}
+
+#[near(serializers = [borsh, json])]
+#[derive(Clone)]
+pub struct RaffleRound {
+    pub round_id: u64,
+    /// Cost of one ticket, in base units. Ticket payments are held in escrow
+    /// while the round is open and burned when it is drawn.
+    pub ticket_price: U128,
+    /// Prize for each winner, in draw order. Reserved from the loot raffle
+    /// pool when the round is created.
+    pub prizes: Vec<U128>,
+    /// Last block height at which entries are accepted.
+    pub close_height: U64,
+    pub status: RaffleStatus,
+    pub total_tickets: U64,
+    pub entry_count: u32,
+    pub winners: Vec<RaffleWinner>,
+    /// Block height the round was drawn at.
+    pub drawn_at_height: Option<U64>,
+    /// `sha256(secret)` committed by the operator before entries closed.
+    /// When set, the round is drawn with `reveal_raffle` instead of `draw_raffle`.
+    pub commitment: Option<Base58CryptoHash>,
+    /// Last block height at which the committed secret can be revealed.
+    pub reveal_deadline: Option<U64>,
+}
+
+/// Sum of a round's prizes.
+pub(crate) fn prize_total(prizes: &[U128]) -> u128 {
+    prizes
+        .iter()
+        .try_fold(0u128, |total, prize| total.checked_add(prize.0))
+        .expect("Prize total overflow")
+}
+
+impl RaffleRound {
+    fn prize_total(&self) -> u128 {
+        prize_total(&self.prizes)
+    }
+
+    fn ticket_escrow(&self) -> u128 {
+        self.ticket_price
+            .0
+            .checked_mul(self.total_tickets.0.into())
+            .expect("Ticket escrow overflow")
+    }
+}
+
+/// A contiguous block of tickets bought in one `enter_raffle` call.
+#[near(serializers = [borsh, json])]
+#[derive(Clone)]
+pub struct RaffleEntry {
+    pub account_id: AccountId,
+    pub first_ticket: U64,
+    pub tickets: U64,
+}
+
+#[near]
+impl Contract {
+    /// Open a raffle round paid from the loot raffle pool. The prize total is
+    /// reserved from the pool straight away and counts against the pool's
+    /// claim limits; rounds above the multisig claim limit must go through
+    /// `propose_action`. `RewardDistributor` only. Returns the round id.
+    #[payable]
+    pub fn create_raffle_round(
+        &mut self,
+        ticket_price: U128,
+        prizes: Vec<U128>,
+        close_height: U64,
+    ) -> u64 {
+        assert_one_yocto();
+        self.assert_role(Role::RewardDistributor);
+        self.multisig_assert_not_required_for_claim(prize_total(&prizes));
+        self.internal_create_raffle_round(ticket_price, prizes, close_height)
+    }
+
+    /// Buy `tickets` tickets in an open round. The ticket cost moves from
+    /// the caller's balance into escrow. Attach enough NEAR to cover the
+    /// storage for the entry; any excess is refunded.
+    #[payable]
+    pub fn enter_raffle(&mut self, round_id: u64, tickets: U64) {
+        self.assert_not_paused(PausableFeature::Raffle);
+        let account_id = env::predecessor_account_id();
+        require!(
+            account_id != env::current_account_id(),
+            "The contract account cannot enter a raffle"
+        );
+        require!(tickets.0 > 0, "Must buy at least one ticket");
+
+        let round = self.internal_raffle_round(round_id);
+        require!(
+            round.status == RaffleStatus::Open && env::block_height() <= round.close_height.0,
+            format!("Raffle round {} is closed for entries", round_id)
+        );
+        let cost = round
+            .ticket_price
+            .0
+            .checked_mul(tickets.0.into())
+            .expect("Ticket cost overflow");
+        let first_ticket = round.total_tickets;
+        let entry_index = round.entry_count;
+
+        let initial_storage = env::storage_usage();
+        let total_tickets = first_ticket
+            .0
+            .checked_add(tickets.0)
+            .expect("Ticket count overflow");
+        if let Some(round) = self.raffle_rounds.get_mut(&round_id) {
+            round.total_tickets = U64(total_tickets);
+            round.entry_count = entry_index.checked_add(1).expect("Entry count overflow");
+        }
+        self.raffle_entries.insert(
+            (round_id, entry_index),
+            RaffleEntry {
+                account_id: account_id.clone(),
+                first_ticket,
+                tickets,
+            },
+        );
+        let held = self
+            .raffle_tickets
+            .get(&(round_id, account_id.clone()))
+            .copied()
+            .unwrap_or(0);
+        self.raffle_tickets
+            .insert((round_id, account_id.clone()), held + tickets.0);
+        self.raffle_rounds.flush();
+        self.raffle_entries.flush();
+        self.raffle_tickets.flush();
+        self.internal_refund_storage_deposit(
+            &account_id,
+            initial_storage,
+            NearToken::from_yoctonear(0),
+        );
+
+        self.token.internal_transfer(
+            &account_id,
+            &env::current_account_id(),
+            cost,
+            Some(format!("Raffle round {} tickets", round_id)),
+        );
+        self.internal_lock_raffle_escrow(cost);
+
+        ContractEvent::RaffleEntered {
+            round_id,
+            account_id,
+            first_ticket,
+            tickets,
+        }
+        .emit();
+    }
+
+    /// Draw the winners of a round once its entries have closed. Anyone can
+    /// call this; the randomness comes from the block the draw lands in,
+    /// which is only known after entries close. Rounds with a committed
+    /// secret are drawn with `reveal_raffle` instead.
+    pub fn draw_raffle(&mut self, round_id: u64) {
+        self.assert_not_paused(PausableFeature::Raffle);
+        let round = self.internal_closed_raffle_round(round_id);
+        require!(
+            round.commitment.is_none(),
+            format!(
+                "Raffle round {} uses commit-reveal; call reveal_raffle",
+                round_id
+            )
+        );
+
+        let seed = env::random_seed_array();
+        self.internal_settle_raffle(round_id, &seed);
+    }
+
+    /// Commit to `sha256(secret)` for an open round, before its entries
+    /// close. The secret must be revealed with `reveal_raffle` by block
+    /// `reveal_deadline`, or entrants can reclaim their tickets.
+    /// `RewardDistributor` only.
+    #[payable]
+    pub fn commit_raffle_secret(
+        &mut self,
+        round_id: u64,
+        commitment: Base58CryptoHash,
+        reveal_deadline: U64,
+    ) {
+        assert_one_yocto();
+        self.assert_role(Role::RewardDistributor);
+        let round = self.internal_raffle_round(round_id);
+        require!(
+            round.status == RaffleStatus::Open && env::block_height() <= round.close_height.0,
+            format!("Raffle round {} is closed for entries", round_id)
+        );
+        require!(
+            round.commitment.is_none(),
+            format!("Raffle round {} already has a commitment", round_id)
+        );
+        require!(
+            reveal_deadline.0 > round.close_height.0,
+            "Reveal deadline must be after the close height"
+        );
+        require!(
+            reveal_deadline.0 - round.close_height.0 <= MAX_REVEAL_WINDOW_BLOCKS,
+            format!(
+                "Reveal deadline must be at most {} blocks after the close height",
+                MAX_REVEAL_WINDOW_BLOCKS
+            )
+        );
+
+        if let Some(round) = self.raffle_rounds.get_mut(&round_id) {
+            round.commitment = Some(commitment);
+            round.reveal_deadline = Some(reveal_deadline);
+        }
+
+        ContractEvent::RaffleSecretCommitted {
+            round_id,
+            commitment,
+            reveal_deadline,
+        }
+        .emit();
+    }
+
+    /// Reveal the committed secret and draw the round. The draw seed is
+    /// `sha256(secret || block random seed)`, so neither the operator nor
+    /// the block producer controls it alone. `RewardDistributor` only.
+    #[payable]
+    pub fn reveal_raffle(&mut self, round_id: u64, secret: Base64VecU8) {
+        assert_one_yocto();
+        self.assert_role(Role::RewardDistributor);
+        self.assert_not_paused(PausableFeature::Raffle);
+        let round = self.internal_closed_raffle_round(round_id);
+        let commitment = round.commitment.unwrap_or_else(|| {
+            env::panic_str(&format!(
+                "Raffle round {} has no commitment; call draw_raffle",
+                round_id
+            ))
+        });
+        let reveal_deadline = round.reveal_deadline.map_or(0, |height| height.0);
+        require!(
+            env::block_height() <= reveal_deadline,
+            format!(
+                "The reveal deadline for raffle round {} passed at block {}",
+                round_id, reveal_deadline
+            )
+        );
+        require!(
+            env::sha256_array(&secret.0) == CryptoHash::from(commitment),
+            "Secret does not match the commitment"
+        );
+
+        let block_seed = env::random_seed_array();
+        let mut data = secret.0.clone();
+        data.extend_from_slice(&block_seed);
+        let seed = env::sha256_array(&data);
+
+        ContractEvent::RaffleSecretRevealed {
+            round_id,
+            secret: hex_encode(&secret.0),
+            block_seed: hex_encode(&block_seed),
+        }
+        .emit();
+        self.internal_settle_raffle(round_id, &seed);
+    }
+
+    /// Reclaim the caller's ticket payments from a round whose secret was
+    /// not revealed by the deadline. The first call also returns the round's
+    /// prizes to the loot raffle pool.
+    pub fn claim_raffle_refund(&mut self, round_id: u64) {
+        self.internal_expire_raffle(round_id);
+
+        let account_id = env::predecessor_account_id();
+        let tickets = self
+            .raffle_tickets
+            .remove(&(round_id, account_id.clone()))
+            .unwrap_or_else(|| {
+                env::panic_str(&format!(
+                    "{} has no tickets to refund in raffle round {}",
+                    account_id, round_id
+                ))
+            });
+        let refund = self
+            .internal_raffle_round(round_id)
+            .ticket_price
+            .0
+            .checked_mul(tickets.into())
+            .expect("Refund overflow");
+        self.internal_unlock_raffle_escrow(refund);
+        self.internal_pay_from_escrow(
+            &account_id,
+            refund,
+            format!("Raffle round {} refund", round_id),
+        );
+
+        ContractEvent::RaffleRefunded {
+            round_id,
+            account_id,
+            amount: U128(refund),
+        }
+        .emit();
+    }
+
+    /// Mark a round whose secret was not revealed by the deadline as
+    /// refunded and return its prizes to the loot raffle pool. Anyone can
+    /// call this; `claim_raffle_refund` does it automatically.
+    pub fn expire_raffle(&mut self, round_id: u64) {
+        self.internal_expire_raffle(round_id);
+    }
+
+    /// A single raffle round
+    pub fn get_raffle_round(&self, round_id: u64) -> Option<RaffleRound> {
+        self.raffle_rounds.get(&round_id).cloned()
+    }
+
+    /// Paginated list of raffle rounds, oldest first
+    pub fn list_raffle_rounds(
+        &self,
+        from_index: Option<u32>,
+        limit: Option<u32>,
+    ) -> Vec<RaffleRound> {
+        let limit = limit
+            .unwrap_or(MAX_RAFFLE_ITEMS_PER_PAGE)
+            .min(MAX_RAFFLE_ITEMS_PER_PAGE);
+        self.raffle_rounds
+            .values()
+            .skip(from_index.unwrap_or(0) as usize)
+            .take(limit as usize)
+            .cloned()
+            .collect()
+    }
+
+    /// Paginated entries of a round, in the order they were made
+    pub fn get_raffle_entries(
+        &self,
+        round_id: u64,
+        from_index: Option<u32>,
+        limit: Option<u32>,
+    ) -> Vec<RaffleEntry> {
+        let entry_count = self
+            .raffle_rounds
+            .get(&round_id)
+            .map_or(0, |round| round.entry_count);
+        let limit = limit
+            .unwrap_or(MAX_RAFFLE_ITEMS_PER_PAGE)
+            .min(MAX_RAFFLE_ITEMS_PER_PAGE);
+        (from_index.unwrap_or(0)..entry_count)
+            .take(limit as usize)
+            .filter_map(|index| self.raffle_entries.get(&(round_id, index)).cloned())
+            .collect()
+    }
+
+    /// Tickets `account_id` holds in a round
+    pub fn get_raffle_tickets(&self, round_id: u64, account_id: AccountId) -> U64 {
+        U64(self
+            .raffle_tickets
+            .get(&(round_id, account_id))
+            .copied()
+            .unwrap_or(0))
+    }
+
+    /// Winners of a drawn round, in prize order
+    pub fn get_raffle_winners(&self, round_id: u64) -> Vec<RaffleWinner> {
+        self.raffle_rounds
+            .get(&round_id)
+            .map(|round| round.winners.clone())
+            .unwrap_or_default()
+    }
+}
+
+impl Contract {
+    /// Validate and open a round once the caller has been authorized.
+    pub(crate) fn internal_create_raffle_round(
+        &mut self,
+        ticket_price: U128,
+        prizes: Vec<U128>,
+        close_height: U64,
+    ) -> u64 {
+        require!(ticket_price.0 > 0, "Ticket price must be greater than zero");
+        require!(
+            !prizes.is_empty() && prizes.len() <= MAX_PRIZES_PER_ROUND,
+            format!("A round must have 1 to {} prizes", MAX_PRIZES_PER_ROUND)
+        );
+        require!(
+            prizes.iter().all(|prize| prize.0 > 0),
+            "Prizes must be greater than zero"
+        );
+        require!(
+            close_height.0 > env::block_height(),
+            "Close height must be in the future"
+        );
+
+        let round_id = self.next_raffle_round_id;
+        self.next_raffle_round_id = round_id.checked_add(1).expect("Round id overflow");
+        let round = RaffleRound {
+            round_id,
+            ticket_price,
+            prizes,
+            close_height,
+            status: RaffleStatus::Open,
+            total_tickets: U64(0),
+            entry_count: 0,
+            winners: Vec::new(),
+            drawn_at_height: None,
+            commitment: None,
+            reveal_deadline: None,
+        };
+        let prize_total = round.prize_total();
+        let prize_amounts: Vec<u128> = round.prizes.iter().map(|prize| prize.0).collect();
+        self.internal_consume_pool_claim_allowance(LOOT_RAFFLE_POOL_ID, &prize_amounts);
+        self.internal_withdraw_from_pool(LOOT_RAFFLE_POOL_ID, prize_total);
+        self.internal_lock_raffle_escrow(prize_total);
+
+        ContractEvent::RaffleRoundCreated {
+            round_id,
+            ticket_price,
+            prizes: round.prizes.clone(),
+            close_height,
+        }
+        .emit();
+        self.raffle_rounds.insert(round_id, round);
+        round_id
+    }
+
+    fn internal_raffle_round(&self, round_id: u64) -> &RaffleRound {
+        self.raffle_rounds
+            .get(&round_id)
+            .unwrap_or_else(|| env::panic_str(&format!("Raffle round {} not found", round_id)))
+    }
+
+    /// Round that is still undrawn and closed for entries.
+    fn internal_closed_raffle_round(&self, round_id: u64) -> RaffleRound {
+        let round = self.internal_raffle_round(round_id);
+        require!(
+            round.status == RaffleStatus::Open,
+            format!("Raffle round {} is not awaiting a draw", round_id)
+        );
+        require!(
+            env::block_height() > round.close_height.0,
+            format!(
+                "Raffle round {} closes at block {}",
+                round_id, round.close_height.0
+            )
+        );
+        round.clone()
+    }
+
+    /// Switch a committed round to `Refunded` once its reveal deadline has
+    /// passed. Does nothing if it already is.
+    fn internal_expire_raffle(&mut self, round_id: u64) {
+        let round = self.internal_raffle_round(round_id);
+        if round.status == RaffleStatus::Refunded {
+            return;
+        }
+        require!(
+            round.status == RaffleStatus::Open,
+            format!("Raffle round {} has already been drawn", round_id)
+        );
+        let reveal_deadline = round.reveal_deadline.unwrap_or_else(|| {
+            env::panic_str(&format!(
+                "Raffle round {} has no commitment to expire",
+                round_id
+            ))
+        });
+        require!(
+            env::block_height() > reveal_deadline.0,
+            format!(
+                "Raffle round {} can still be revealed until block {}",
+                round_id, reveal_deadline.0
+            )
+        );
+
+        let prize_total = round.prize_total();
+        self.internal_unlock_raffle_escrow(prize_total);
+        self.internal_return_to_raffle_pool(prize_total);
+        if let Some(round) = self.raffle_rounds.get_mut(&round_id) {
+            round.status = RaffleStatus::Refunded;
+        }
+
+        ContractEvent::RaffleExpired {
+            round_id,
+            returned_prizes: U128(prize_total),
+        }
+        .emit();
+    }
+
+    pub(crate) fn internal_lock_raffle_escrow(&mut self, amount: u128) {
+        self.raffle_escrow = U128(
+            self.raffle_escrow
+                .0
+                .checked_add(amount)
+                .expect("Raffle escrow overflow"),
+        );
+    }
+
+    pub(crate) fn internal_unlock_raffle_escrow(&mut self, amount: u128) {
+        self.raffle_escrow = U128(
+            self.raffle_escrow
+                .0
+                .checked_sub(amount)
+                .expect("Raffle escrow underflow"),
+        );
+    }
+
+    /// Pick winners for a closed round from `seed`, pay their prizes, return
+    /// unawarded prizes to the loot raffle pool and burn the ticket payments.
+    /// Winners who have since unregistered from storage are skipped.
+    pub(crate) fn internal_settle_raffle(&mut self, round_id: u64, seed: &[u8; 32]) {
+        let round = self.internal_raffle_round(round_id).clone();
+        let total_tickets = round.total_tickets.0;
+
+        let mut drawn: Vec<u64> = Vec::new();
+        let mut winners = Vec::new();
+        for (index, prize) in round.prizes.iter().enumerate() {
+            if drawn.len() as u64 >= total_tickets {
+                break;
+            }
+            // Each ticket wins at most once: step past tickets already drawn.
+            let mut ticket = random_ticket(seed, index as u32, total_tickets);
+            while drawn.contains(&ticket) {
+                ticket = (ticket + 1) % total_tickets;
+            }
+            drawn.push(ticket);
+            let entry = self.internal_raffle_entry_for_ticket(round_id, round.entry_count, ticket);
+            let paid = self.token.accounts.contains_key(&entry.account_id);
+            winners.push(RaffleWinner {
+                account_id: entry.account_id,
+                ticket: U64(ticket),
+                prize: *prize,
+                paid,
+            });
+        }
+
+        let prize_total = round.prize_total();
+        let awarded = winners
+            .iter()
+            .filter(|winner| winner.paid)
+            .map(|winner| winner.prize.0)
+            .sum::<u128>();
+        let ticket_escrow = round.ticket_escrow();
+        self.internal_unlock_raffle_escrow(prize_total + ticket_escrow);
+
+        let memo = format!("Raffle round {} prize", round_id);
+        for winner in winners.iter().filter(|winner| winner.paid) {
+            self.internal_pay_from_escrow(&winner.account_id, winner.prize.0, memo.clone());
+            self.internal_record_claim(
+                &winner.account_id,
+                LOOT_RAFFLE_POOL_ID,
+                winner.prize.0,
+                ClaimSource::Raffle,
+            );
+        }
+        if prize_total > awarded {
+            self.internal_return_to_raffle_pool(prize_total - awarded);
+        }
+        self.internal_burn_from_escrow(
+            ticket_escrow,
+            &format!("Raffle round {} tickets", round_id),
+        );
+
+        if let Some(round) = self.raffle_rounds.get_mut(&round_id) {
+            round.status = RaffleStatus::Drawn;
+            round.winners = winners.clone();
+            round.drawn_at_height = Some(U64(env::block_height()));
+        }
+
+        ContractEvent::RaffleDrawn {
+            round_id,
+            random_seed: hex_encode(seed),
+            winners,
+        }
+        .emit();
+    }
+
+    /// Give back prize reservations the round could not award. A closed pool
+    /// cannot take them, so they stay unlocked in the contract account.
+    fn internal_return_to_raffle_pool(&mut self, amount: u128) {
+        if let Some(pool) = self
+            .pools
+            .get_mut(&LOOT_RAFFLE_POOL_ID)
+            .filter(|pool| !pool.closed)
+        {
+            pool.amount = U128(
+                pool.amount
+                    .0
+                    .checked_add(amount)
+                    .expect("Pool amount overflow"),
+            );
+        }
+    }
+
+    /// Binary search for the entry holding `ticket`; entries are stored in
+    /// ascending ticket order.
+    fn internal_raffle_entry_for_ticket(
+        &self,
+        round_id: u64,
+        entry_count: u32,
+        ticket: u64,
+    ) -> RaffleEntry {
+        let (mut low, mut high) = (0u32, entry_count);
+        while high - low > 1 {
+            let mid = low + (high - low) / 2;
+            let entry = self
+                .raffle_entries
+                .get(&(round_id, mid))
+                .expect("Missing raffle entry");
+            if entry.first_ticket.0 <= ticket {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+        self.raffle_entries
+            .get(&(round_id, low))
+            .cloned()
+            .expect("Missing raffle entry")
+    }
+}
+
+/// Ticket number for prize `index`: `sha256(seed || index as u32 LE)`, read
+/// as a little-endian u128 from its first 16 bytes, modulo `total_tickets`.
+fn random_ticket(seed: &[u8; 32], index: u32, total_tickets: u64) -> u64 {
+    let mut data = [0u8; 36];
+    data[..32].copy_from_slice(seed);
+    data[32..].copy_from_slice(&index.to_le_bytes());
+    let hash = env::sha256_array(&data);
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(&hash[..16]);
+    (u128::from_le_bytes(bytes) % u128::from(total_tickets)) as u64
+}
+
+fn hex_encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
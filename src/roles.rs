@@ -0,0 +1,157 @@
+use near_sdk::{env, near, require, AccountId, BorshStorageKey};
+use near_sdk_contract_tools::rbac::Rbac;
+
+use crate::events::ContractEvent;
+use crate::{assert_one_yocto, Contract, ContractExt};
+
+/// Privileged roles. `Owner` manages role membership and follows contract
+/// ownership; the other roles each gate one area of the contract.
+#[derive(BorshStorageKey, Clone, Copy, PartialEq, Debug)]
+#[near(serializers = [borsh, json])]
+pub enum Role {
+    Owner,
+    /// Runs `mint`.
+    Minter,
+    /// Pays out rewards from the pools.
+    RewardDistributor,
+    /// Guardian allowed to pause and unpause the contract.
+    Pauser,
+    /// Changes emission parameters and pool definitions.
+    ConfigAdmin,
+    /// Game backend that reports tap counts.
+    GameServer,
+}
+
+impl Role {
+    pub const ALL: [Role; 6] = [
+        Role::Owner,
+        Role::Minter,
+        Role::RewardDistributor,
+        Role::Pauser,
+        Role::ConfigAdmin,
+        Role::GameServer,
+    ];
+}
+
+#[near]
+impl Contract {
+    /// Grant `role` to `account_id`. Owner only.
+    #[payable]
+    pub fn grant_role(&mut self, account_id: AccountId, role: Role) {
+        assert_one_yocto();
+        self.assert_role(Role::Owner);
+        require!(
+            role != Role::Owner,
+            "The Owner role moves with ownership transfers and cannot be granted directly"
+        );
+        require!(
+            !Self::account_has_role(&account_id, role),
+            format!("{} already has the {:?} role", account_id, role)
+        );
+
+        self.add_role(&account_id, &role);
+        ContractEvent::RoleGranted {
+            account_id,
+            role,
+            granted_by: env::predecessor_account_id(),
+        }
+        .emit();
+    }
+
+    /// Revoke `role` from `account_id`. Owner only.
+    #[payable]
+    pub fn revoke_role(&mut self, account_id: AccountId, role: Role) {
+        assert_one_yocto();
+        self.assert_role(Role::Owner);
+        require!(
+            role != Role::Owner,
+            "The Owner role moves with ownership transfers and cannot be revoked directly"
+        );
+        require!(
+            Self::account_has_role(&account_id, role),
+            format!("{} does not have the {:?} role", account_id, role)
+        );
+
+        self.remove_role(&account_id, &role);
+        ContractEvent::RoleRevoked {
+            account_id,
+            role,
+            revoked_by: env::predecessor_account_id(),
+        }
+        .emit();
+    }
+
+    /// Whether `account_id` holds `role`
+    pub fn has_role(&self, account_id: AccountId, role: Role) -> bool {
+        Self::account_has_role(&account_id, role)
+    }
+
+    /// All accounts holding `role`
+    pub fn get_role_members(&self, role: Role) -> Vec<AccountId> {
+        <Self as Rbac>::iter_members_of(&role).collect()
+    }
+}
+
+impl Contract {
+    pub(crate) fn account_has_role(account_id: &AccountId, role: Role) -> bool {
+        <Self as Rbac>::has_role(account_id, &role)
+    }
+
+    /// Require the predecessor to hold `role`.
+    pub(crate) fn assert_role(&self, role: Role) {
+        let caller_id = env::predecessor_account_id();
+        require!(
+            Self::account_has_role(&caller_id, role),
+            format!("Caller {} does not have the {:?} role", caller_id, role)
+        );
+    }
+
+    /// Give `account_id` every role and announce it; used at initialization.
+    pub(crate) fn internal_grant_all_roles(&mut self, account_id: &AccountId) {
+        for role in Role::ALL {
+            self.add_role(account_id, &role);
+            ContractEvent::RoleGranted {
+                account_id: account_id.clone(),
+                role,
+                granted_by: account_id.clone(),
+            }
+            .emit();
+        }
+    }
+
+    /// Strip every role from the previous owner and hand the Owner role to
+    /// the new one.
+    pub(crate) fn internal_transfer_owner_role(
+        &mut self,
+        previous_owner: &AccountId,
+        new_owner: &AccountId,
+    ) {
+        self.internal_revoke_all_roles(previous_owner, new_owner);
+        self.add_role(new_owner, &Role::Owner);
+        ContractEvent::RoleGranted {
+            account_id: new_owner.clone(),
+            role: Role::Owner,
+            granted_by: previous_owner.clone(),
+        }
+        .emit();
+    }
+
+    /// Remove every role `account_id` holds, emitting an event for each.
+    pub(crate) fn internal_revoke_all_roles(
+        &mut self,
+        account_id: &AccountId,
+        revoked_by: &AccountId,
+    ) {
+        for role in Role::ALL {
+            if Self::account_has_role(account_id, role) {
+                self.remove_role(account_id, &role);
+                ContractEvent::RoleRevoked {
+                    account_id: account_id.clone(),
+                    role,
+                    revoked_by: revoked_by.clone(),
+                }
+                .emit();
+            }
+        }
+    }
+}
@@ -0,0 +1,511 @@
+use near_contract_standards::fungible_token::receiver::FungibleTokenReceiver;
+use near_sdk::json_types::{U128, U64};
+use near_sdk::{env, near, require, AccountId, NearToken, Promise, PromiseOrValue};
+
+use crate::events::ContractEvent;
+use crate::pause::PausableFeature;
+use crate::roles::Role;
+use crate::timelock::TimelockAction;
+use crate::{assert_one_yocto, Contract, ContractExt, EMISSION_EPOCH_NS};
+
+/// `ft_transfer_call` message that stakes the transferred tokens.
+const STAKE_MSG: &str = "stake";
+
+/// Fixed-point scale of `reward_per_token`.
+const REWARD_PER_TOKEN_PRECISION: u128 = 1_000_000_000_000;
+
+const MAX_BASIS_POINTS: u16 = 10_000;
+
+/// Longest unstake cooldown that can be configured: 30 days, in nanoseconds.
+const MAX_UNSTAKE_COOLDOWN_NS: u64 = 30 * 24 * 60 * 60 * 1_000_000_000;
+
+const YEAR_NS: u64 = 365 * 24 * 60 * 60 * 1_000_000_000;
+
+#[near(serializers = [borsh, json])]
+#[derive(Clone, Default)]
+pub struct StakingConfig {
+    /// Share of every `mint`, in basis points, that funds staking rewards
+    /// instead of going to the owner.
+    pub reward_share_bps: u16,
+    /// Time (nanoseconds) unstaked tokens wait before they can be withdrawn.
+    /// Zero returns them straight away.
+    pub unstake_cooldown: U64,
+}
+
+/// Accumulator state shared by all stakers.
+#[near(serializers = [borsh, json])]
+#[derive(Clone, Default)]
+pub struct StakingState {
+    pub total_staked: U128,
+    /// Rewards per staked base unit so far, scaled by 10^12.
+    pub reward_per_token: U128,
+    /// Minted rewards not yet claimed, including any not yet streamed to stakers.
+    pub reward_escrow: U128,
+    /// Rounding leftovers, added back into the stream by the next mint.
+    pub undistributed_rewards: U128,
+    /// Minted rewards still to be streamed to stakers.
+    pub streaming_rewards: U128,
+    /// Time (nanoseconds) the stream releases its last rewards. The stream
+    /// is pushed back while nothing is staked.
+    pub stream_ends_at: U64,
+    /// Time (nanoseconds) the stream was last released into the accumulator.
+    pub rewards_updated_at: U64,
+    /// Rewards of the most recent emission epoch.
+    pub last_epoch_rewards: U128,
+    /// Unstaked tokens still in cooldown.
+    pub total_unstaking: U128,
+}
+
+#[near(serializers = [borsh])]
+#[derive(Clone, Default)]
+pub struct StakeAccount {
+    pub staked: u128,
+    pub reward_per_token_paid: u128,
+    /// Rewards accrued up to `reward_per_token_paid` and not yet claimed.
+    pub rewards: u128,
+    pub unstaking: u128,
+    pub unstake_available_at: u64,
+}
+
+impl StakeAccount {
+    /// Whether the account holds nothing, so its storage can be released.
+    fn is_empty(&self) -> bool {
+        self.staked == 0 && self.rewards == 0 && self.unstaking == 0
+    }
+
+    /// Move rewards earned since the last update into `rewards`.
+    fn accrue(&mut self, reward_per_token: u128) {
+        let earned = self
+            .staked
+            .checked_mul(reward_per_token - self.reward_per_token_paid)
+            .expect("Staking reward overflow")
+            / REWARD_PER_TOKEN_PRECISION;
+        self.rewards = self
+            .rewards
+            .checked_add(earned)
+            .expect("Staking reward overflow");
+        self.reward_per_token_paid = reward_per_token;
+    }
+}
+
+/// Stake position of one account
+#[near(serializers = [json])]
+pub struct StakeInfo {
+    pub account_id: AccountId,
+    pub staked: U128,
+    pub pending_rewards: U128,
+    pub unstaking: U128,
+    /// When the unstaking amount can be withdrawn (nanoseconds), if any is in cooldown.
+    pub unstake_available_at: Option<U64>,
+    /// Whether the account has paid for its stake storage.
+    pub storage_paid: bool,
+}
+
+#[near]
+impl Contract {
+    /// Queue a new staking config. `ConfigAdmin` only. Returns the queued
+    /// action id.
+    #[payable]
+    pub fn set_staking_config(&mut self, config: StakingConfig) -> u64 {
+        assert_one_yocto();
+        self.assert_role(Role::ConfigAdmin);
+        require!(
+            config.reward_share_bps <= MAX_BASIS_POINTS,
+            format!(
+                "Staking reward share must not exceed {} basis points",
+                MAX_BASIS_POINTS
+            )
+        );
+        require!(
+            config.unstake_cooldown.0 <= MAX_UNSTAKE_COOLDOWN_NS,
+            format!(
+                "Unstake cooldown must not exceed {} ns",
+                MAX_UNSTAKE_COOLDOWN_NS
+            )
+        );
+
+        self.internal_queue_action(TimelockAction::SetStakingConfig { config })
+    }
+
+    /// Pay for the caller's stake entry, which staking requires. Any deposit
+    /// above the storage cost is refunded; nothing is charged twice.
+    #[payable]
+    pub fn stake_storage_deposit(&mut self) {
+        let account_id = env::predecessor_account_id();
+        if self.stakes.contains_key(&account_id) {
+            let attached = env::attached_deposit();
+            if !attached.is_zero() {
+                Promise::new(account_id).transfer(attached);
+            }
+            return;
+        }
+
+        let initial_storage = env::storage_usage();
+        self.stakes
+            .insert(account_id.clone(), StakeAccount::default());
+        self.stakes.flush();
+        self.internal_refund_storage_deposit(
+            &account_id,
+            initial_storage,
+            NearToken::from_yoctonear(0),
+        );
+    }
+
+    /// Remove the caller's stake entry and refund its storage. Nothing may
+    /// be staked, unstaking or unclaimed.
+    #[payable]
+    pub fn stake_storage_unregister(&mut self) {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        require!(
+            self.stakes.contains_key(&account_id),
+            "Stake storage is not paid"
+        );
+        let stake = self.internal_accrued_stake(&account_id);
+        require!(
+            stake.is_empty(),
+            "Unstake, withdraw and claim everything before unregistering"
+        );
+
+        let initial_storage = env::storage_usage();
+        self.stakes.remove(&account_id);
+        self.stakes.flush();
+        let released = initial_storage.saturating_sub(env::storage_usage());
+        Promise::new(account_id).transfer(env::storage_byte_cost().saturating_mul(released.into()));
+    }
+
+    /// Unstake `amount`. It is returned straight away, or after the cooldown
+    /// through `withdraw_unstaked`; unstaking more restarts the cooldown.
+    #[payable]
+    pub fn unstake(&mut self, amount: U128) {
+        assert_one_yocto();
+        self.assert_not_paused(PausableFeature::Staking);
+        let account_id = env::predecessor_account_id();
+        require!(amount.0 > 0, "Unstake amount must be greater than zero");
+
+        let mut stake = self.internal_accrued_stake(&account_id);
+        require!(
+            stake.staked >= amount.0,
+            format!(
+                "Insufficient stake. Staked: {}, Requested: {}",
+                stake.staked, amount.0
+            )
+        );
+        stake.staked -= amount.0;
+        self.staking.total_staked = U128(self.staking.total_staked.0 - amount.0);
+
+        let cooldown = self.staking_config.unstake_cooldown.0;
+        let available_at = env::block_timestamp().saturating_add(cooldown);
+        if cooldown == 0 {
+            self.internal_pay_from_escrow(&account_id, amount.0, "Unstake".to_string());
+        } else {
+            stake.unstaking = stake
+                .unstaking
+                .checked_add(amount.0)
+                .expect("Unstaking amount overflow");
+            stake.unstake_available_at = available_at;
+            self.staking.total_unstaking = U128(
+                self.staking
+                    .total_unstaking
+                    .0
+                    .checked_add(amount.0)
+                    .expect("Unstaking amount overflow"),
+            );
+        }
+        self.internal_save_stake(&account_id, stake);
+
+        ContractEvent::Unstaked {
+            account_id,
+            amount,
+            available_at: U64(available_at),
+        }
+        .emit();
+    }
+
+    /// Return unstaked tokens whose cooldown has passed.
+    #[payable]
+    pub fn withdraw_unstaked(&mut self) -> U128 {
+        assert_one_yocto();
+        self.assert_not_paused(PausableFeature::Staking);
+        let account_id = env::predecessor_account_id();
+        let mut stake = self.internal_accrued_stake(&account_id);
+        require!(stake.unstaking > 0, "Nothing is unstaking");
+        let now = env::block_timestamp();
+        require!(
+            now >= stake.unstake_available_at,
+            format!(
+                "Unstaked tokens are in cooldown until {}, current time {}",
+                stake.unstake_available_at, now
+            )
+        );
+
+        let amount = stake.unstaking;
+        stake.unstaking = 0;
+        self.staking.total_unstaking = U128(self.staking.total_unstaking.0 - amount);
+        self.internal_save_stake(&account_id, stake);
+        self.internal_pay_from_escrow(&account_id, amount, "Withdraw unstaked".to_string());
+
+        ContractEvent::UnstakeWithdrawn {
+            account_id,
+            amount: U128(amount),
+        }
+        .emit();
+        U128(amount)
+    }
+
+    /// Pay out the caller's accrued staking rewards.
+    #[payable]
+    pub fn claim_staking_rewards(&mut self) -> U128 {
+        assert_one_yocto();
+        self.assert_not_paused(PausableFeature::Staking);
+        let account_id = env::predecessor_account_id();
+        let mut stake = self.internal_accrued_stake(&account_id);
+        let amount = stake.rewards;
+        require!(amount > 0, "No staking rewards to claim");
+
+        stake.rewards = 0;
+        self.staking.reward_escrow = U128(self.staking.reward_escrow.0 - amount);
+        self.internal_save_stake(&account_id, stake);
+        self.internal_pay_from_escrow(&account_id, amount, "Staking rewards".to_string());
+
+        ContractEvent::StakingRewardsClaimed {
+            account_id,
+            amount: U128(amount),
+        }
+        .emit();
+        U128(amount)
+    }
+
+    /// Current staking config
+    pub fn get_staking_config(&self) -> StakingConfig {
+        self.staking_config.clone()
+    }
+
+    /// Totals and accumulator of the staking module
+    pub fn get_staking_state(&self) -> StakingState {
+        self.staking.clone()
+    }
+
+    /// Stake, pending rewards and cooldown of `account_id`
+    pub fn get_stake(&self, account_id: AccountId) -> StakeInfo {
+        let (_, increase) = self.internal_streamed_rewards(env::block_timestamp());
+        let mut stake = self.stakes.get(&account_id).cloned().unwrap_or_default();
+        stake.accrue(self.staking.reward_per_token.0 + increase);
+        let storage_paid = self.stakes.contains_key(&account_id);
+        StakeInfo {
+            account_id,
+            staked: U128(stake.staked),
+            pending_rewards: U128(stake.rewards),
+            unstaking: U128(stake.unstaking),
+            unstake_available_at: (stake.unstaking > 0).then_some(U64(stake.unstake_available_at)),
+            storage_paid,
+        }
+    }
+
+    /// Annual yield in basis points if the last epoch's staking rewards
+    /// continued at the current total stake. Zero while nothing is staked.
+    pub fn get_staking_apr(&self) -> U128 {
+        let total_staked = self.staking.total_staked.0;
+        if total_staked == 0 {
+            return U128(0);
+        }
+        U128(
+            self.staking
+                .last_epoch_rewards
+                .0
+                .checked_mul(u128::from(YEAR_NS) * u128::from(MAX_BASIS_POINTS))
+                .expect("APR overflow")
+                / u128::from(EMISSION_EPOCH_NS)
+                / total_staked,
+        )
+    }
+}
+
+#[near]
+impl FungibleTokenReceiver for Contract {
+    /// Stake tokens sent with `ft_transfer_call(contract, amount, memo, "stake")`.
+    /// Only this contract's own token is accepted, and the sender must have
+    /// paid for its stake entry with `stake_storage_deposit`; otherwise the
+    /// transfer fails and the tokens are returned.
+    fn ft_on_transfer(
+        &mut self,
+        sender_id: AccountId,
+        amount: U128,
+        msg: String,
+    ) -> PromiseOrValue<U128> {
+        require!(
+            env::predecessor_account_id() == env::current_account_id(),
+            "Only this token can be staked"
+        );
+        require!(
+            msg == STAKE_MSG,
+            format!("Unsupported message {:?}, expected {:?}", msg, STAKE_MSG)
+        );
+        self.assert_not_paused(PausableFeature::Staking);
+        require!(amount.0 > 0, "Stake amount must be greater than zero");
+        require!(
+            self.stakes.contains_key(&sender_id),
+            "Call stake_storage_deposit before staking"
+        );
+
+        let mut stake = self.internal_accrued_stake(&sender_id);
+        stake.staked = stake
+            .staked
+            .checked_add(amount.0)
+            .expect("Stake amount overflow");
+        self.staking.total_staked = U128(
+            self.staking
+                .total_staked
+                .0
+                .checked_add(amount.0)
+                .expect("Stake amount overflow"),
+        );
+        self.internal_save_stake(&sender_id, stake);
+
+        ContractEvent::Staked {
+            account_id: sender_id,
+            amount,
+        }
+        .emit();
+        PromiseOrValue::Value(U128(0))
+    }
+}
+
+impl Contract {
+    pub(crate) fn internal_set_staking_config(&mut self, config: StakingConfig) {
+        self.staking_config = config;
+    }
+
+    /// Staking rewards share of an epoch emission of `emission`.
+    pub(crate) fn internal_staking_share(&self, emission: u128) -> u128 {
+        emission
+            .checked_mul(self.staking_config.reward_share_bps.into())
+            .expect("Staking share overflow")
+            / u128::from(MAX_BASIS_POINTS)
+    }
+
+    /// Stream `rewards`, minted over `epochs` epochs, to stakers over the
+    /// next emission epoch, together with whatever the previous stream has
+    /// not released yet. Streaming keeps a stake placed just before a mint
+    /// from collecting a whole epoch's rewards at once.
+    pub(crate) fn internal_fund_staking_rewards(&mut self, rewards: u128, epochs: u64) {
+        if rewards == 0 {
+            return;
+        }
+        self.internal_update_staking_rewards();
+        self.staking.last_epoch_rewards = U128(rewards / u128::from(epochs.max(1)));
+        self.staking.reward_escrow = U128(
+            self.staking
+                .reward_escrow
+                .0
+                .checked_add(rewards)
+                .expect("Staking reward escrow overflow"),
+        );
+
+        let now = env::block_timestamp();
+        self.staking.streaming_rewards = U128(
+            self.staking
+                .streaming_rewards
+                .0
+                .checked_add(self.staking.undistributed_rewards.0)
+                .and_then(|streaming| streaming.checked_add(rewards))
+                .expect("Staking reward overflow"),
+        );
+        self.staking.undistributed_rewards = U128(0);
+        self.staking.stream_ends_at = U64(now.saturating_add(EMISSION_EPOCH_NS));
+        self.staking.rewards_updated_at = U64(now);
+
+        ContractEvent::StakingRewardsFunded {
+            amount: U128(rewards),
+            reward_per_token: self.staking.reward_per_token,
+        }
+        .emit();
+    }
+
+    /// Rewards the stream has released by `now` since the last update, and
+    /// the resulting increase in `reward_per_token`, which also spreads any
+    /// rounding leftovers. Both are zero while nothing is staked.
+    fn internal_streamed_rewards(&self, now: u64) -> (u128, u128) {
+        let total_staked = self.staking.total_staked.0;
+        if total_staked == 0 {
+            return (0, 0);
+        }
+        let (updated_at, ends_at) = (
+            self.staking.rewards_updated_at.0,
+            self.staking.stream_ends_at.0,
+        );
+        let released = if now > updated_at && ends_at > updated_at {
+            self.staking
+                .streaming_rewards
+                .0
+                .checked_mul((now.min(ends_at) - updated_at).into())
+                .expect("Staking reward overflow")
+                / u128::from(ends_at - updated_at)
+        } else {
+            0
+        };
+        let increase = self
+            .staking
+            .undistributed_rewards
+            .0
+            .checked_add(released)
+            .and_then(|pending| pending.checked_mul(REWARD_PER_TOKEN_PRECISION))
+            .expect("Reward per token overflow")
+            / total_staked;
+        (released, increase)
+    }
+
+    /// Release what the stream owes up to now into the accumulator. While
+    /// nothing is staked the stream is paused by pushing its end back.
+    fn internal_update_staking_rewards(&mut self) {
+        let now = env::block_timestamp();
+        let (updated_at, ends_at) = (
+            self.staking.rewards_updated_at.0,
+            self.staking.stream_ends_at.0,
+        );
+        let total_staked = self.staking.total_staked.0;
+        if total_staked == 0 {
+            if ends_at > updated_at {
+                self.staking.stream_ends_at =
+                    U64(ends_at.saturating_add(now.saturating_sub(updated_at)));
+            }
+        } else {
+            let (released, increase) = self.internal_streamed_rewards(now);
+            let pending = self.staking.undistributed_rewards.0 + released;
+            let distributed = increase * total_staked / REWARD_PER_TOKEN_PRECISION;
+            self.staking.reward_per_token = U128(
+                self.staking
+                    .reward_per_token
+                    .0
+                    .checked_add(increase)
+                    .expect("Reward per token overflow"),
+            );
+            self.staking.undistributed_rewards = U128(pending - distributed);
+            self.staking.streaming_rewards = U128(self.staking.streaming_rewards.0 - released);
+        }
+        self.staking.rewards_updated_at = U64(now.max(updated_at));
+    }
+
+    /// Tokens held for stakers: stakes, cooldowns and unclaimed rewards.
+    pub(crate) fn internal_staking_locked_amount(&self) -> u128 {
+        self.staking
+            .total_staked
+            .0
+            .checked_add(self.staking.total_unstaking.0)
+            .and_then(|total| total.checked_add(self.staking.reward_escrow.0))
+            .expect("Staking total overflow")
+    }
+
+    fn internal_accrued_stake(&mut self, account_id: &AccountId) -> StakeAccount {
+        self.internal_update_staking_rewards();
+        let mut stake = self.stakes.get(account_id).cloned().unwrap_or_default();
+        stake.accrue(self.staking.reward_per_token.0);
+        stake
+    }
+
+    /// Entries stay until `stake_storage_unregister`, since their storage
+    /// has been paid for.
+    fn internal_save_stake(&mut self, account_id: &AccountId, stake: StakeAccount) {
+        self.stakes.insert(account_id.clone(), stake);
+    }
+}
@@ -0,0 +1,256 @@
+use near_sdk::json_types::{U128, U64};
+use near_sdk::{env, near, require};
+
+use crate::events::ContractEvent;
+use crate::pause::PausableFeature;
+use crate::pools::GLOBAL_TAPPING_POOL_ID;
+use crate::roles::Role;
+use crate::timelock::TimelockAction;
+use crate::{assert_one_yocto, Contract, ContractExt, EMISSION_EPOCH_NS};
+
+const DAY_NS: u64 = 24 * 60 * 60 * 1_000_000_000;
+
+/// How often the tapping pool is refilled on its own schedule.
+#[near(serializers = [borsh, json])]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum RefillPeriod {
+    Daily,
+    Weekly,
+    /// 30 days, the same length as an emission epoch.
+    Monthly,
+}
+
+impl RefillPeriod {
+    pub fn duration_ns(&self) -> u64 {
+        match self {
+            RefillPeriod::Daily => DAY_NS,
+            RefillPeriod::Weekly => 7 * DAY_NS,
+            RefillPeriod::Monthly => 30 * DAY_NS,
+        }
+    }
+}
+
+/// What happens to whatever the previous period left unclaimed.
+#[near(serializers = [borsh, json])]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum RemainderPolicy {
+    Burn,
+    /// Carry it over on top of the new refill.
+    Rollover,
+    /// Send it to the owner account.
+    ReturnToTreasury,
+}
+
+#[near(serializers = [borsh, json])]
+#[derive(Clone)]
+pub struct TappingRefillConfig {
+    pub period: RefillPeriod,
+    /// Amount (base units) the pool is refilled with every period.
+    pub amount: U128,
+    pub remainder: RemainderPolicy,
+}
+
+/// Active refill schedule for the global tapping pool.
+#[near(serializers = [borsh, json])]
+#[derive(Clone)]
+pub struct TappingRefillSchedule {
+    pub config: TappingRefillConfig,
+    /// Start (nanoseconds) of period 0, when the schedule took effect.
+    pub anchor: U64,
+    /// Latest period the pool has been refilled for.
+    pub last_period: U64,
+    /// Tokens set aside out of each epoch's emission for upcoming refills.
+    /// Refills draw from here and never mint on their own.
+    pub reserve: U128,
+}
+
+impl TappingRefillSchedule {
+    fn period_at(&self, timestamp: u64) -> u64 {
+        timestamp.saturating_sub(self.anchor.0) / self.config.period.duration_ns()
+    }
+
+    /// Reserve that covers every refill due in one emission epoch.
+    pub(crate) fn reserve_target(&self) -> u128 {
+        let periods = EMISSION_EPOCH_NS.div_ceil(self.config.period.duration_ns());
+        self.config
+            .amount
+            .0
+            .checked_mul(periods.into())
+            .expect("Refill reserve overflow")
+    }
+
+    /// Start (nanoseconds) of the period after the last refill.
+    pub fn next_refill_at(&self) -> u64 {
+        self.anchor.0.saturating_add(
+            (self.last_period.0 + 1).saturating_mul(self.config.period.duration_ns()),
+        )
+    }
+}
+
+#[near]
+impl Contract {
+    /// Queue a refill schedule for the global tapping pool, replacing its
+    /// refill on `mint`. `None` hands refills back to `mint`. `ConfigAdmin`
+    /// only. Returns the queued action id.
+    #[payable]
+    pub fn set_tapping_refill_schedule(&mut self, config: Option<TappingRefillConfig>) -> u64 {
+        assert_one_yocto();
+        self.assert_role(Role::ConfigAdmin);
+        if let Some(config) = &config {
+            require!(
+                config.amount.0 > 0,
+                "Refill amount must be greater than zero"
+            );
+        }
+
+        self.internal_queue_action(TimelockAction::SetTappingRefillSchedule { config })
+    }
+
+    /// Apply a due tapping pool refill. Claims from the tapping pool do this
+    /// automatically; anyone may call it to refill ahead of the next claim.
+    /// Refills only move tokens out of the emission-funded reserve.
+    pub fn refill_tapping_pool(&mut self) {
+        self.internal_apply_tapping_refill();
+    }
+
+    /// Active tapping pool refill schedule, if any
+    pub fn get_tapping_refill_schedule(&self) -> Option<TappingRefillSchedule> {
+        self.tapping_refill.clone()
+    }
+
+    /// When the next scheduled tapping pool refill becomes due (nanoseconds)
+    pub fn get_next_tapping_refill_time(&self) -> Option<U64> {
+        self.tapping_refill
+            .as_ref()
+            .map(|schedule| U64(schedule.next_refill_at()))
+    }
+}
+
+impl Contract {
+    pub(crate) fn internal_set_tapping_refill_schedule(
+        &mut self,
+        config: Option<TappingRefillConfig>,
+    ) {
+        let reserve = self.internal_tapping_reserve_amount();
+        self.tapping_refill = config.map(|config| TappingRefillSchedule {
+            config,
+            anchor: U64(env::block_timestamp()),
+            last_period: U64(0),
+            reserve: U128(reserve),
+        });
+
+        // Refills return to `mint`, so the reserve is no longer needed
+        if self.tapping_refill.is_none()
+            && reserve > 0
+            && self.owner_id != env::current_account_id()
+        {
+            let owner_id = self.owner_id.clone();
+            self.internal_pay_from_escrow(
+                &owner_id,
+                reserve,
+                "Tapping refill reserve release".to_string(),
+            );
+        }
+    }
+
+    pub(crate) fn internal_tapping_reserve_amount(&self) -> u128 {
+        self.tapping_refill
+            .as_ref()
+            .map_or(0, |schedule| schedule.reserve.0)
+    }
+
+    /// Top the refill reserve up towards one epoch's worth of refills out of
+    /// `budget`, and return the amount added.
+    pub(crate) fn internal_fund_tapping_reserve(&mut self, budget: &mut u128) -> u128 {
+        let pool_open = self
+            .pools
+            .get(&GLOBAL_TAPPING_POOL_ID)
+            .is_some_and(|pool| !pool.closed);
+        let Some(schedule) = self.tapping_refill.as_mut().filter(|_| pool_open) else {
+            return 0;
+        };
+        let top_up = schedule
+            .reserve_target()
+            .saturating_sub(schedule.reserve.0)
+            .min(*budget);
+        *budget -= top_up;
+        schedule.reserve = U128(schedule.reserve.0 + top_up);
+        top_up
+    }
+
+    /// Apply a due tapping pool refill before paying out of `pool_id`.
+    pub(crate) fn internal_refill_before_claim(&mut self, pool_id: u32) {
+        if pool_id == GLOBAL_TAPPING_POOL_ID {
+            self.internal_apply_tapping_refill();
+        }
+    }
+
+    /// Refill the tapping pool from the reserve if a new period has started
+    /// since the last refill. Missed periods do not stack: the pool is
+    /// refilled once. While `Mint` is paused or the reserve is empty the
+    /// refill waits, and a reserve short of the full amount refills what it holds.
+    pub(crate) fn internal_apply_tapping_refill(&mut self) {
+        if self.is_paused(PausableFeature::Mint) {
+            return;
+        }
+        let Some(schedule) = &self.tapping_refill else {
+            return;
+        };
+        let period = schedule.period_at(env::block_timestamp());
+        if period <= schedule.last_period.0 || schedule.reserve.0 == 0 {
+            return;
+        }
+        let config = schedule.config.clone();
+        let refill = config.amount.0.min(schedule.reserve.0);
+        if let Some(schedule) = self.tapping_refill.as_mut() {
+            schedule.last_period = U64(period);
+        }
+        let Some(pool) = self
+            .pools
+            .get_mut(&GLOBAL_TAPPING_POOL_ID)
+            .filter(|pool| !pool.closed)
+        else {
+            return;
+        };
+        if let Some(schedule) = self.tapping_refill.as_mut() {
+            schedule.reserve = U128(schedule.reserve.0 - refill);
+        }
+
+        let remainder = pool.amount.0;
+        pool.amount = match config.remainder {
+            RemainderPolicy::Rollover => U128(
+                remainder
+                    .checked_add(refill)
+                    .expect("Refill amount overflow"),
+            ),
+            RemainderPolicy::Burn | RemainderPolicy::ReturnToTreasury => U128(refill),
+        };
+        pool.total_amount = U128(
+            pool.total_amount
+                .0
+                .checked_add(pool.amount.0)
+                .expect("Total amount addition overflow"),
+        );
+
+        let memo = format!("Tapping pool refill for period {}", period);
+        if config.remainder == RemainderPolicy::Burn {
+            self.internal_burn_from_escrow(remainder, &memo);
+        }
+        if config.remainder == RemainderPolicy::ReturnToTreasury
+            && remainder > 0
+            && self.owner_id != env::current_account_id()
+        {
+            let owner_id = self.owner_id.clone();
+            self.internal_pay_from_escrow(&owner_id, remainder, memo.clone());
+        }
+
+        ContractEvent::TappingPoolRefilled {
+            pool_id: GLOBAL_TAPPING_POOL_ID,
+            period: U64(period),
+            amount: U128(refill),
+            remainder: U128(remainder),
+            remainder_policy: config.remainder,
+        }
+        .emit();
+    }
+}
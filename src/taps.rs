@@ -0,0 +1,138 @@
+use near_sdk::borsh;
+use near_sdk::json_types::{Base64VecU8, U64};
+use near_sdk::{env, near, require, AccountId, CurveType, NearToken, PublicKey};
+
+use crate::events::ContractEvent;
+use crate::pause::PausableFeature;
+use crate::pools::GLOBAL_TAPPING_POOL_ID;
+use crate::roles::Role;
+use crate::timelock::TimelockAction;
+use crate::vouchers::verify_ed25519_signature;
+use crate::{assert_one_yocto, Contract, ContractExt};
+
+/// Most accounts a single `submit_taps` call can report.
+const MAX_TAP_SUBMISSIONS_PER_CALL: usize = 100;
+
+/// Domain tag in signed tap reports, so no other signed message can be
+/// passed off as one.
+const TAP_REPORT_DOMAIN: &str = "taps";
+
+/// An account's tap count for one epoch, signed off-chain by the tap signer.
+///
+/// `taps` is the account's running total for the epoch, so a report can be
+/// resubmitted without counting twice. The signature covers
+/// `borsh((contract_account_id, "taps", epoch, account_id, taps))`.
+#[near(serializers = [json])]
+pub struct TapReport {
+    pub account_id: AccountId,
+    pub taps: U64,
+    pub signature: Base64VecU8,
+}
+
+#[near]
+impl Contract {
+    /// Queue a change to the key tap reports must be signed with. `None`
+    /// disables tap submissions. Owner only. Returns the queued action id.
+    #[payable]
+    pub fn set_tap_signer(&mut self, public_key: Option<PublicKey>) -> u64 {
+        assert_one_yocto();
+        self.assert_role(Role::Owner);
+        if let Some(public_key) = &public_key {
+            require!(
+                public_key.curve_type() == CurveType::ED25519,
+                "Tap signer must be an ed25519 key"
+            );
+        }
+
+        self.internal_queue_action(TimelockAction::SetTapSigner { public_key })
+    }
+
+    /// Key tap reports must currently be signed with, if any
+    pub fn get_tap_signer(&self) -> Option<PublicKey> {
+        self.tap_signer.clone()
+    }
+
+    /// Record signed tap counts for the tapping pool's open distribution
+    /// epoch; each tap is one unit of weight. Reports at or below what an
+    /// account already has are ignored. `GameServer` only. Attach enough NEAR
+    /// to cover the storage for new weight records; any excess is refunded.
+    #[payable]
+    pub fn submit_taps(&mut self, epoch: u64, reports: Vec<TapReport>) {
+        self.assert_not_paused(PausableFeature::Taps);
+        self.assert_role(Role::GameServer);
+        let current_epoch = self.internal_current_distribution_epoch(GLOBAL_TAPPING_POOL_ID);
+        require!(
+            epoch == current_epoch,
+            format!(
+                "Taps can only be submitted for the current epoch {}",
+                current_epoch
+            )
+        );
+        require!(
+            reports.len() <= MAX_TAP_SUBMISSIONS_PER_CALL,
+            format!(
+                "At most {} accounts can be submitted per call",
+                MAX_TAP_SUBMISSIONS_PER_CALL
+            )
+        );
+        let tap_signer = self
+            .tap_signer
+            .clone()
+            .unwrap_or_else(|| env::panic_str("Tap submissions are disabled"));
+
+        let initial_storage = env::storage_usage();
+        let mut accounts: u32 = 0;
+        let mut submitted: u64 = 0;
+        for report in &reports {
+            let message = borsh::to_vec(&(
+                env::current_account_id(),
+                TAP_REPORT_DOMAIN,
+                epoch,
+                &report.account_id,
+                report.taps.0,
+            ))
+            .expect("Failed to serialize tap report");
+            require!(
+                verify_ed25519_signature(&tap_signer, &message, &report.signature.0),
+                format!("Invalid tap report signature for {}", report.account_id)
+            );
+
+            let recorded = self
+                .epoch_weights
+                .get(&(GLOBAL_TAPPING_POOL_ID, epoch, report.account_id.clone()))
+                .copied()
+                .unwrap_or(0);
+            let new_taps = u128::from(report.taps.0).saturating_sub(recorded);
+            if new_taps == 0 {
+                continue;
+            }
+            self.internal_accrue_weight(GLOBAL_TAPPING_POOL_ID, &report.account_id, new_taps);
+            accounts += 1;
+            submitted = submitted
+                .checked_add(new_taps as u64)
+                .expect("Tap count overflow");
+        }
+        self.epoch_weights.flush();
+        self.epoch_distributions.flush();
+        let submitted_by = env::predecessor_account_id();
+        self.internal_refund_storage_deposit(
+            &submitted_by,
+            initial_storage,
+            NearToken::from_yoctonear(0),
+        );
+
+        ContractEvent::TapsSubmitted {
+            epoch,
+            submitted_by,
+            accounts,
+            taps: U64(submitted),
+        }
+        .emit();
+    }
+}
+
+impl Contract {
+    pub(crate) fn internal_set_tap_signer(&mut self, public_key: Option<PublicKey>) {
+        self.tap_signer = public_key;
+    }
+}
@@ -0,0 +1,276 @@
+use near_contract_standards::fungible_token::metadata::FungibleTokenMetadata;
+use near_sdk::json_types::U64;
+use near_sdk::{env, near, require, AccountId, PublicKey};
+
+use crate::events::ContractEvent;
+use crate::limits::ClaimLimits;
+use crate::multisig::MultisigConfig;
+use crate::pools::{PoolKind, RefillPolicy};
+use crate::roles::Role;
+use crate::staking::StakingConfig;
+use crate::tapping::TappingRefillConfig;
+use crate::{assert_one_yocto, Contract, ContractExt, EmissionConfig};
+
+/// Default delay between queueing and executing a privileged action: 2 days, in nanoseconds.
+pub const DEFAULT_TIMELOCK_DELAY_NS: u64 = 2 * 24 * 60 * 60 * 1_000_000_000;
+
+/// Shortest delay the timelock can be configured with: 1 day, in nanoseconds.
+const MIN_TIMELOCK_DELAY_NS: u64 = 24 * 60 * 60 * 1_000_000_000;
+
+/// Longest delay the timelock can be configured with: 30 days, in nanoseconds.
+const MAX_TIMELOCK_DELAY_NS: u64 = 30 * 24 * 60 * 60 * 1_000_000_000;
+
+/// How long a queued action stays executable after its ETA: 14 days, in nanoseconds.
+const TIMELOCK_GRACE_PERIOD_NS: u64 = 14 * 24 * 60 * 60 * 1_000_000_000;
+
+/// Most queued actions returned by a single `get_queued_actions` call.
+const MAX_QUEUED_ACTIONS_PER_PAGE: u32 = 100;
+
+/// Privileged changes that only take effect after the timelock delay.
+#[near(serializers = [borsh, json])]
+#[derive(Clone)]
+pub enum TimelockAction {
+    TransferOwnership {
+        new_owner: AccountId,
+    },
+    RenounceOwnership,
+    UpdateEmissionConfig {
+        config: EmissionConfig,
+    },
+    UpdateMetadata {
+        metadata: FungibleTokenMetadata,
+    },
+    CreatePool {
+        kind: PoolKind,
+        refill_policy: RefillPolicy,
+    },
+    SetPoolRefillPolicy {
+        pool_id: u32,
+        refill_policy: RefillPolicy,
+    },
+    ClosePool {
+        pool_id: u32,
+    },
+    SetTimelockDelay {
+        delay: U64,
+    },
+    SetMultisigConfig {
+        config: MultisigConfig,
+    },
+    SetVoucherSigner {
+        public_key: Option<PublicKey>,
+    },
+    SetPoolClaimLimits {
+        pool_id: u32,
+        limits: ClaimLimits,
+    },
+    SetTappingRefillSchedule {
+        config: Option<TappingRefillConfig>,
+    },
+    SetStakingConfig {
+        config: StakingConfig,
+    },
+    SetTapSigner {
+        public_key: Option<PublicKey>,
+    },
+}
+
+impl TimelockAction {
+    /// Role required both to queue and to execute the action.
+    fn required_role(&self) -> Role {
+        match self {
+            TimelockAction::TransferOwnership { .. }
+            | TimelockAction::RenounceOwnership
+            | TimelockAction::SetTimelockDelay { .. }
+            | TimelockAction::SetMultisigConfig { .. }
+            | TimelockAction::SetVoucherSigner { .. }
+            | TimelockAction::SetTapSigner { .. } => Role::Owner,
+            TimelockAction::UpdateEmissionConfig { .. }
+            | TimelockAction::UpdateMetadata { .. }
+            | TimelockAction::CreatePool { .. }
+            | TimelockAction::SetPoolRefillPolicy { .. }
+            | TimelockAction::ClosePool { .. }
+            | TimelockAction::SetPoolClaimLimits { .. }
+            | TimelockAction::SetTappingRefillSchedule { .. }
+            | TimelockAction::SetStakingConfig { .. } => Role::ConfigAdmin,
+        }
+    }
+}
+
+#[near(serializers = [borsh, json])]
+#[derive(Clone)]
+pub struct QueuedAction {
+    pub action_id: u64,
+    pub action: TimelockAction,
+    pub proposed_by: AccountId,
+    pub queued_at: U64,
+    /// Earliest time (nanoseconds) the action can be executed.
+    pub eta: U64,
+    /// Time (nanoseconds) after which the action can no longer be executed.
+    pub expires_at: U64,
+}
+
+#[near]
+impl Contract {
+    /// Queue a change to the timelock delay itself. Owner only. Returns the
+    /// queued action id.
+    #[payable]
+    pub fn set_timelock_delay(&mut self, delay: U64) -> u64 {
+        assert_one_yocto();
+        self.assert_role(Role::Owner);
+        require!(
+            (MIN_TIMELOCK_DELAY_NS..=MAX_TIMELOCK_DELAY_NS).contains(&delay.0),
+            format!(
+                "Timelock delay must be between {} ns and {} ns",
+                MIN_TIMELOCK_DELAY_NS, MAX_TIMELOCK_DELAY_NS
+            )
+        );
+
+        self.internal_queue_action(TimelockAction::SetTimelockDelay { delay })
+    }
+
+    /// Apply a queued action once its ETA has passed. The caller needs the
+    /// same role that was required to queue it. An action past its execution
+    /// window is dropped from the queue instead of being applied.
+    #[payable]
+    pub fn execute_timelock_action(&mut self, action_id: u64) {
+        assert_one_yocto();
+        let queued = self
+            .queued_actions
+            .remove(&action_id)
+            .unwrap_or_else(|| env::panic_str(&format!("Action {} is not queued", action_id)));
+        self.assert_role(queued.action.required_role());
+
+        let now = env::block_timestamp();
+        require!(
+            now >= queued.eta.0,
+            format!(
+                "Action {} is timelocked until {}, current time {}",
+                action_id, queued.eta.0, now
+            )
+        );
+        if now > queued.expires_at.0 {
+            ContractEvent::TimelockActionExpired {
+                action_id,
+                expired_at: queued.expires_at,
+            }
+            .emit();
+            return;
+        }
+
+        match queued.action {
+            TimelockAction::TransferOwnership { new_owner } => {
+                self.internal_propose_ownership_transfer(new_owner)
+            }
+            TimelockAction::RenounceOwnership => self.internal_renounce_ownership(),
+            TimelockAction::UpdateEmissionConfig { config } => {
+                self.internal_update_emission_config(config)
+            }
+            TimelockAction::UpdateMetadata { metadata } => self.internal_update_metadata(metadata),
+            TimelockAction::CreatePool {
+                kind,
+                refill_policy,
+            } => self.internal_create_pool(kind, refill_policy),
+            TimelockAction::SetPoolRefillPolicy {
+                pool_id,
+                refill_policy,
+            } => self.internal_set_pool_refill_policy(pool_id, refill_policy),
+            TimelockAction::ClosePool { pool_id } => self.internal_close_pool(pool_id),
+            TimelockAction::SetTimelockDelay { delay } => self.timelock_delay = delay,
+            TimelockAction::SetMultisigConfig { config } => {
+                self.internal_set_multisig_config(config)
+            }
+            TimelockAction::SetVoucherSigner { public_key } => {
+                self.internal_set_voucher_signer(public_key)
+            }
+            TimelockAction::SetPoolClaimLimits { pool_id, limits } => {
+                self.internal_set_pool_claim_limits(pool_id, limits)
+            }
+            TimelockAction::SetTappingRefillSchedule { config } => {
+                self.internal_set_tapping_refill_schedule(config)
+            }
+            TimelockAction::SetStakingConfig { config } => self.internal_set_staking_config(config),
+            TimelockAction::SetTapSigner { public_key } => self.internal_set_tap_signer(public_key),
+        }
+
+        ContractEvent::TimelockActionExecuted {
+            action_id,
+            executed_by: env::predecessor_account_id(),
+        }
+        .emit();
+    }
+
+    /// Drop a queued action before it executes. Pauser (guardian) only.
+    #[payable]
+    pub fn cancel_timelock_action(&mut self, action_id: u64) {
+        assert_one_yocto();
+        self.assert_role(Role::Pauser);
+        require!(
+            self.queued_actions.remove(&action_id).is_some(),
+            format!("Action {} is not queued", action_id)
+        );
+
+        ContractEvent::TimelockActionCancelled {
+            action_id,
+            cancelled_by: env::predecessor_account_id(),
+        }
+        .emit();
+    }
+
+    /// Paginated list of actions waiting in the timelock
+    pub fn get_queued_actions(
+        &self,
+        from_index: Option<u32>,
+        limit: Option<u32>,
+    ) -> Vec<QueuedAction> {
+        let limit = limit
+            .unwrap_or(MAX_QUEUED_ACTIONS_PER_PAGE)
+            .min(MAX_QUEUED_ACTIONS_PER_PAGE);
+        self.queued_actions
+            .values()
+            .skip(from_index.unwrap_or(0) as usize)
+            .take(limit as usize)
+            .cloned()
+            .collect()
+    }
+
+    /// Current timelock delay, in nanoseconds
+    pub fn get_timelock_delay(&self) -> U64 {
+        self.timelock_delay
+    }
+}
+
+impl Contract {
+    /// Put `action` in the timelock queue and return its id. Callers check
+    /// the proposer's permissions and validate the action first.
+    pub(crate) fn internal_queue_action(&mut self, action: TimelockAction) -> u64 {
+        let action_id = self.next_action_id;
+        self.next_action_id = action_id.checked_add(1).expect("Action id overflow");
+
+        let queued_at = env::block_timestamp();
+        let eta = queued_at
+            .checked_add(self.timelock_delay.0)
+            .expect("ETA overflow");
+        let expires_at = eta
+            .checked_add(TIMELOCK_GRACE_PERIOD_NS)
+            .expect("Expiry overflow");
+        let queued = QueuedAction {
+            action_id,
+            action,
+            proposed_by: env::predecessor_account_id(),
+            queued_at: U64(queued_at),
+            eta: U64(eta),
+            expires_at: U64(expires_at),
+        };
+        self.queued_actions.insert(action_id, queued.clone());
+
+        ContractEvent::TimelockActionQueued {
+            action_id,
+            action: queued.action,
+            proposed_by: queued.proposed_by,
+            eta: queued.eta,
+        }
+        .emit();
+        action_id
+    }
+}
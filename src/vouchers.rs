@@ -0,0 +1,192 @@
+use near_contract_standards::storage_management::StorageManagement;
+use near_sdk::borsh;
+use near_sdk::json_types::{Base64VecU8, U128, U64};
+use near_sdk::{env, near, require, AccountId, CurveType, NearToken, Promise, PublicKey};
+
+use crate::events::ContractEvent;
+use crate::history::ClaimSource;
+use crate::pause::PausableFeature;
+use crate::roles::Role;
+use crate::timelock::TimelockAction;
+use crate::{assert_one_yocto, Contract, ContractExt};
+
+/// A reward payout signed off-chain by the registered voucher signer.
+///
+/// The signature covers `borsh((contract_account_id, voucher))`, so a
+/// voucher issued for one deployment cannot be replayed on another.
+#[near(serializers = [borsh, json])]
+#[derive(Clone)]
+pub struct RewardVoucher {
+    pub pool_id: u32,
+    pub recipient: AccountId,
+    pub amount: U128,
+    /// Single-use id; every nonce can be redeemed once per contract.
+    pub nonce: U64,
+    /// Time (nanoseconds) after which the voucher can no longer be redeemed.
+    pub expires_at: U64,
+}
+
+#[near]
+impl Contract {
+    /// Queue a change to the key vouchers must be signed with. `None`
+    /// disables voucher redemption. Owner only. Returns the queued action id.
+    #[payable]
+    pub fn set_voucher_signer(&mut self, public_key: Option<PublicKey>) -> u64 {
+        assert_one_yocto();
+        self.assert_role(Role::Owner);
+        if let Some(public_key) = &public_key {
+            require!(
+                public_key.curve_type() == CurveType::ED25519,
+                "Voucher signer must be an ed25519 key"
+            );
+        }
+
+        self.internal_queue_action(TimelockAction::SetVoucherSigner { public_key })
+    }
+
+    /// Redeem a signed voucher. Must be called by the voucher's recipient,
+    /// who attaches enough NEAR to cover the storage the redemption uses
+    /// (the nonce record, claim limit usage and claim history, plus
+    /// registration if needed). Any excess is refunded.
+    #[payable]
+    pub fn redeem_voucher(&mut self, voucher: RewardVoucher, signature: Base64VecU8) {
+        self.assert_not_paused(PausableFeature::ClaimRewards);
+        let recipient = env::predecessor_account_id();
+        require!(
+            voucher.recipient == recipient,
+            "Only the voucher recipient can redeem it"
+        );
+        require!(
+            recipient != env::current_account_id(),
+            "The contract account cannot claim rewards"
+        );
+        require!(voucher.amount.0 > 0, "Invalid amount to claim");
+        require!(
+            env::block_timestamp() <= voucher.expires_at.0,
+            format!("Voucher {} has expired", voucher.nonce.0)
+        );
+        self.multisig_assert_not_required_for_claim(voucher.amount.0);
+        self.assert_voucher_signature(&voucher, &signature.0);
+
+        // Registration is charged at the standard minimum, which is what
+        // `storage_unregister` refunds, so it is kept out of the measurement
+        let registration_cost = if self.token.accounts.get(&recipient).is_none() {
+            self.token.internal_register_account(&recipient);
+            self.token.storage_balance_bounds().min
+        } else {
+            NearToken::from_yoctonear(0)
+        };
+
+        let initial_storage = env::storage_usage();
+        require!(
+            self.used_voucher_nonces.insert(&voucher.nonce.0),
+            format!("Voucher {} has already been redeemed", voucher.nonce.0)
+        );
+        self.internal_refill_before_claim(voucher.pool_id);
+        self.internal_consume_claim_allowance(voucher.pool_id, &recipient, voucher.amount.0);
+        self.internal_withdraw_from_pool(voucher.pool_id, voucher.amount.0);
+        self.internal_pay_from_escrow(
+            &recipient,
+            voucher.amount.0,
+            format!(
+                "Reward voucher {} from pool_id: {}",
+                voucher.nonce.0, voucher.pool_id
+            ),
+        );
+        self.internal_record_claim(
+            &recipient,
+            voucher.pool_id,
+            voucher.amount.0,
+            ClaimSource::Voucher,
+        );
+        // Measure once every record the redemption adds has been written
+        self.pool_epoch_claims.flush();
+        self.user_epoch_claims.flush();
+        self.user_reward_stats.flush();
+        self.recent_claims.flush();
+        self.internal_refund_storage_deposit(&recipient, initial_storage, registration_cost);
+
+        ContractEvent::VoucherRedeemed {
+            pool_id: voucher.pool_id,
+            recipient,
+            amount: voucher.amount,
+            nonce: voucher.nonce,
+        }
+        .emit();
+    }
+
+    /// Key vouchers must currently be signed with, if any
+    pub fn get_voucher_signer(&self) -> Option<PublicKey> {
+        self.voucher_signer.clone()
+    }
+
+    /// Whether a voucher nonce has already been redeemed
+    pub fn is_voucher_redeemed(&self, nonce: U64) -> bool {
+        self.used_voucher_nonces.contains(&nonce.0)
+    }
+}
+
+/// Whether `signature` is `public_key`'s ed25519 signature of `message`.
+/// Panics if the key or the signature is malformed.
+pub(crate) fn verify_ed25519_signature(
+    public_key: &PublicKey,
+    message: &[u8],
+    signature: &[u8],
+) -> bool {
+    let public_key: &[u8; 32] = public_key.as_bytes()[1..]
+        .try_into()
+        .expect("Invalid ed25519 signer key");
+    let signature: &[u8; 64] = signature
+        .try_into()
+        .unwrap_or_else(|_| env::panic_str("Signature must be 64 bytes"));
+    env::ed25519_verify(signature, message, public_key)
+}
+
+impl Contract {
+    pub(crate) fn internal_set_voucher_signer(&mut self, public_key: Option<PublicKey>) {
+        self.voucher_signer = public_key;
+    }
+
+    fn assert_voucher_signature(&self, voucher: &RewardVoucher, signature: &[u8]) {
+        require!(
+            self.voucher_signer.is_some(),
+            "Voucher redemption is disabled"
+        );
+        let message = borsh::to_vec(&(env::current_account_id(), voucher))
+            .expect("Failed to serialize voucher");
+        let public_key = self
+            .voucher_signer
+            .as_ref()
+            .unwrap_or_else(|| env::panic_str("No voucher signer is set"));
+        require!(
+            verify_ed25519_signature(public_key, &message, signature),
+            "Invalid voucher signature"
+        );
+    }
+
+    /// Charge `payer` for the storage used since `initial_storage`, plus
+    /// `extra_cost`, out of the attached deposit and refund the rest.
+    pub(crate) fn internal_refund_storage_deposit(
+        &self,
+        payer: &AccountId,
+        initial_storage: u64,
+        extra_cost: NearToken,
+    ) {
+        let used = env::storage_usage().saturating_sub(initial_storage);
+        let required = env::storage_byte_cost()
+            .saturating_mul(used.into())
+            .saturating_add(extra_cost);
+        let attached = env::attached_deposit();
+        require!(
+            attached >= required,
+            format!(
+                "Attached deposit must cover {} of storage",
+                required.exact_amount_display()
+            )
+        );
+        let refund = attached.saturating_sub(required);
+        if !refund.is_zero() {
+            Promise::new(payer.clone()).transfer(refund);
+        }
+    }
+}